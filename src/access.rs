@@ -0,0 +1,99 @@
+use std::any::{type_name, TypeId};
+
+/// The component types a system reads and writes, used to find systems that can run at the
+/// same time.
+#[derive(Clone, Debug, Default)]
+pub struct Access {
+    reads: Vec<(TypeId, &'static str)>,
+    writes: Vec<(TypeId, &'static str)>,
+}
+
+impl Access {
+    pub fn new() -> Self {
+        Access::default()
+    }
+
+    pub fn add_read<T: 'static>(&mut self) {
+        let type_id = TypeId::of::<T>();
+        if !self.reads.iter().any(|(read, _)| *read == type_id) {
+            self.reads.push((type_id, type_name::<T>()));
+        }
+    }
+
+    pub fn add_write<T: 'static>(&mut self) {
+        let type_id = TypeId::of::<T>();
+        if !self.writes.iter().any(|(write, _)| *write == type_id) {
+            self.writes.push((type_id, type_name::<T>()));
+        }
+    }
+
+    pub fn reads(&self) -> impl Iterator<Item = TypeId> + '_ {
+        self.reads.iter().map(|(type_id, _)| *type_id)
+    }
+
+    pub fn writes(&self) -> impl Iterator<Item = TypeId> + '_ {
+        self.writes.iter().map(|(type_id, _)| *type_id)
+    }
+
+    /// Adds the reads and writes of `other`.
+    pub fn extend(&mut self, other: &Access) {
+        for read in &other.reads {
+            if !self.reads.contains(read) {
+                self.reads.push(*read);
+            }
+        }
+        for write in &other.writes {
+            if !self.writes.contains(write) {
+                self.writes.push(*write);
+            }
+        }
+    }
+
+    /// Whether systems with the two accesses can run at the same time, which is when neither
+    /// writes a type the other reads or writes.
+    pub fn is_compatible(&self, other: &Access) -> bool {
+        self.conflicts(other).next().is_none()
+    }
+
+    /// Names of the types one of the accesses writes and the other reads or writes.
+    pub fn conflicts<'a>(&'a self, other: &'a Access) -> impl Iterator<Item = &'static str> + 'a {
+        let writes_read = |writes: &'a [(TypeId, &'static str)], access: &'a Access| {
+            writes.iter().filter(move |(type_id, _)| {
+                access
+                    .reads()
+                    .chain(access.writes())
+                    .any(|other| other == *type_id)
+            })
+        };
+        writes_read(&self.writes, other)
+            .chain(writes_read(&other.writes, self))
+            .map(|(_, name)| *name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Access;
+
+    struct Health;
+    struct Name;
+
+    #[test]
+    fn writes_conflict_with_reads_and_writes() {
+        let mut reads_health = Access::new();
+        reads_health.add_read::<Health>();
+        let mut also_reads_health = Access::new();
+        also_reads_health.add_read::<Health>();
+        also_reads_health.add_write::<Name>();
+        let mut writes_health = Access::new();
+        writes_health.add_write::<Health>();
+
+        assert!(reads_health.is_compatible(&also_reads_health));
+        assert!(!reads_health.is_compatible(&writes_health));
+        assert!(!writes_health.is_compatible(&writes_health));
+        assert_eq!(
+            writes_health.conflicts(&reads_health).collect::<Vec<_>>(),
+            vec![std::any::type_name::<Health>()]
+        );
+    }
+}
@@ -0,0 +1,236 @@
+use std::any::TypeId;
+use std::collections::HashMap;
+
+use crate::Entity;
+
+/// Where an entity's components live: the archetype of its component set and its row in that
+/// archetype's columns.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EntityLocation {
+    pub(crate) archetype: usize,
+    pub(crate) row: usize,
+}
+
+impl EntityLocation {
+    pub(crate) const EMPTY: EntityLocation = EntityLocation {
+        archetype: 0,
+        row: 0,
+    };
+}
+
+/// The set of entities that have exactly the same component types. Their components are stored
+/// in one column per type, with each entity at the same row in every column.
+pub struct Archetype {
+    types: Vec<TypeId>,
+    entities: Vec<Entity>,
+    // Archetypes reached by adding or removing one component type, cached so moving entities
+    // does not need to sort and hash type lists every time
+    add_edges: HashMap<TypeId, usize>,
+    remove_edges: HashMap<TypeId, usize>,
+    // Archetypes reached by inserting a bundle, keyed by the bundle's type
+    bundle_edges: HashMap<TypeId, usize>,
+}
+
+impl Archetype {
+    fn new(types: Vec<TypeId>) -> Self {
+        Archetype {
+            types,
+            entities: Vec::new(),
+            add_edges: HashMap::new(),
+            remove_edges: HashMap::new(),
+            bundle_edges: HashMap::new(),
+        }
+    }
+
+    pub fn contains(&self, type_id: TypeId) -> bool {
+        self.types.binary_search(&type_id).is_ok()
+    }
+
+    pub fn types(&self) -> &[TypeId] {
+        &self.types
+    }
+
+    pub fn entities(&self) -> &[Entity] {
+        &self.entities
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    /// Adds the entity as the last row, returning the row.
+    pub(crate) fn push(&mut self, entity: Entity) -> usize {
+        self.entities.push(entity);
+        self.entities.len() - 1
+    }
+
+    pub(crate) fn reserve(&mut self, additional: usize) {
+        self.entities.reserve(additional);
+    }
+
+    /// Removes the entity at `row` by moving the last entity into its place, the same way the
+    /// columns are updated. Returns the entity that was moved, if any.
+    pub(crate) fn swap_remove(&mut self, row: usize) -> Option<Entity> {
+        self.entities.swap_remove(row);
+        self.entities.get(row).copied()
+    }
+}
+
+pub(crate) struct Archetypes {
+    archetypes: Vec<Archetype>,
+    ids: HashMap<Vec<TypeId>, usize>,
+}
+
+impl Archetypes {
+    pub fn new() -> Self {
+        // Archetype 0 holds entities without any components
+        let mut ids = HashMap::new();
+        ids.insert(Vec::new(), 0);
+        Archetypes {
+            archetypes: vec![Archetype::new(Vec::new())],
+            ids,
+        }
+    }
+
+    pub fn get(&self, archetype: usize) -> &Archetype {
+        &self.archetypes[archetype]
+    }
+
+    pub fn get_mut(&mut self, archetype: usize) -> &mut Archetype {
+        &mut self.archetypes[archetype]
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Archetype> {
+        self.archetypes.iter()
+    }
+
+    /// Returns the archetype with the given sorted set of types, creating it if needed.
+    pub fn get_or_insert(&mut self, types: Vec<TypeId>) -> usize {
+        if let Some(&archetype) = self.ids.get(&types) {
+            return archetype;
+        }
+
+        let archetype = self.archetypes.len();
+        self.archetypes.push(Archetype::new(types.clone()));
+        self.ids.insert(types, archetype);
+        archetype
+    }
+
+    /// Returns the archetype with the types of `archetype` plus `type_id`.
+    pub fn with_added(&mut self, archetype: usize, type_id: TypeId) -> usize {
+        if let Some(&target) = self.archetypes[archetype].add_edges.get(&type_id) {
+            return target;
+        }
+
+        let mut types = self.archetypes[archetype].types.clone();
+        if let Err(index) = types.binary_search(&type_id) {
+            types.insert(index, type_id);
+        }
+        let target = self.get_or_insert(types);
+        self.archetypes[archetype].add_edges.insert(type_id, target);
+        target
+    }
+
+    /// Returns the archetype with the types of `archetype` plus the table component types that
+    /// `add_types` adds for the bundle.
+    pub fn with_bundle(
+        &mut self,
+        archetype: usize,
+        bundle: TypeId,
+        add_types: impl FnOnce(&mut Vec<TypeId>),
+    ) -> usize {
+        if let Some(&target) = self.archetypes[archetype].bundle_edges.get(&bundle) {
+            return target;
+        }
+
+        let mut types = self.archetypes[archetype].types.clone();
+        add_types(&mut types);
+        types.sort();
+        types.dedup();
+        let target = self.get_or_insert(types);
+        self.archetypes[archetype]
+            .bundle_edges
+            .insert(bundle, target);
+        target
+    }
+
+    /// Returns the archetype with the types of `archetype` minus `type_id`.
+    pub fn with_removed(&mut self, archetype: usize, type_id: TypeId) -> usize {
+        if let Some(&target) = self.archetypes[archetype].remove_edges.get(&type_id) {
+            return target;
+        }
+
+        let mut types = self.archetypes[archetype].types.clone();
+        if let Ok(index) = types.binary_search(&type_id) {
+            types.remove(index);
+        }
+        let target = self.get_or_insert(types);
+        self.archetypes[archetype]
+            .remove_edges
+            .insert(type_id, target);
+        target
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Component, Entity, World};
+
+    struct Position(i32);
+    impl Component for Position {}
+    struct Velocity(i32);
+    impl Component for Velocity {}
+
+    #[test]
+    fn entities_with_the_same_components_share_an_archetype() {
+        let mut world = World::new();
+        let entities: Vec<Entity> = (0..3)
+            .map(|i| {
+                let entity = world.new_entity();
+                world.add_component_to_entity(entity, Position(i));
+                world.add_component_to_entity(entity, Velocity(i));
+                entity
+            })
+            .collect();
+
+        let archetype = world
+            .archetypes()
+            .find(|archetype| archetype.types().len() == 2)
+            .unwrap();
+        assert_eq!(archetype.entities(), &entities[..]);
+    }
+
+    #[test]
+    fn moving_entities_keeps_their_components() {
+        let mut world = World::new();
+        let first = world.new_entity();
+        world.add_component_to_entity(first, Position(1));
+        world.add_component_to_entity(first, Velocity(1));
+        let second = world.new_entity();
+        world.add_component_to_entity(second, Position(2));
+        world.add_component_to_entity(second, Velocity(2));
+        let third = world.new_entity();
+        world.add_component_to_entity(third, Position(3));
+        world.add_component_to_entity(third, Velocity(3));
+
+        // Removing from the first row moves the last entity of each column into its place
+        assert_eq!(world.remove_component::<Velocity>(first).unwrap().0, 1);
+        assert_eq!(world.get::<Position>(first).unwrap().0, 1);
+        assert_eq!(world.get::<Velocity>(third).unwrap().0, 3);
+
+        world.despawn(second);
+        assert_eq!(world.get::<Position>(third).unwrap().0, 3);
+        assert_eq!(world.get::<Velocity>(third).unwrap().0, 3);
+
+        let positions: Vec<i32> = world
+            .query::<&Position>()
+            .iter()
+            .map(|position| position.0)
+            .collect();
+        assert_eq!(positions, vec![1, 3]);
+    }
+}
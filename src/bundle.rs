@@ -0,0 +1,320 @@
+use std::any::{type_name, TypeId};
+
+use crate::archetype::EntityLocation;
+use crate::{Component, Entity, StorageType, World};
+
+/// A set of components inserted together, so the entity moves to its final archetype once.
+///
+/// Implemented for every component, for tuples of up to eight bundles, and for structs
+/// declared with the `bundle!` macro.
+pub trait Bundle: Send + Sync + 'static {
+    /// Adds the types of the bundle's components that are stored in archetype tables.
+    fn table_types(types: &mut Vec<TypeId>);
+
+    /// Adds the types of all of the bundle's components, whatever their storage.
+    fn component_types(types: &mut Vec<TypeId>);
+
+    /// Writes each component of the bundle to the entity.
+    fn write(self, writer: &mut BundleWriter<'_>);
+}
+
+impl<C: Component> Bundle for C {
+    fn table_types(types: &mut Vec<TypeId>) {
+        if C::STORAGE == StorageType::Table {
+            types.push(TypeId::of::<C>());
+        }
+    }
+
+    fn component_types(types: &mut Vec<TypeId>) {
+        types.push(TypeId::of::<C>());
+    }
+
+    fn write(self, writer: &mut BundleWriter<'_>) {
+        writer.push(self);
+    }
+}
+
+macro_rules! impl_bundle_tuple {
+    ($($name:ident),*) => {
+        #[allow(non_snake_case, unused_variables)]
+        impl<$($name: Bundle),*> Bundle for ($($name,)*) {
+            fn table_types(types: &mut Vec<TypeId>) {
+                $($name::table_types(types);)*
+            }
+
+            fn component_types(types: &mut Vec<TypeId>) {
+                $($name::component_types(types);)*
+            }
+
+            fn write(self, writer: &mut BundleWriter<'_>) {
+                let ($($name,)*) = self;
+                $($name.write(writer);)*
+            }
+        }
+    };
+}
+
+impl_bundle_tuple!();
+impl_bundle_tuple!(A);
+impl_bundle_tuple!(A, B);
+impl_bundle_tuple!(A, B, C);
+impl_bundle_tuple!(A, B, C, D);
+impl_bundle_tuple!(A, B, C, D, E);
+impl_bundle_tuple!(A, B, C, D, E, F);
+impl_bundle_tuple!(A, B, C, D, E, F, G);
+impl_bundle_tuple!(A, B, C, D, E, F, G, H);
+
+/// Declares a struct whose fields are bundles and implements `Bundle` for it.
+///
+/// ```
+/// use becs::{bundle, Component, World};
+///
+/// struct Position(f32, f32);
+/// impl Component for Position {}
+/// struct Velocity(f32, f32);
+/// impl Component for Velocity {}
+///
+/// bundle! {
+///     pub struct MoverBundle {
+///         pub position: Position,
+///         pub velocity: Velocity,
+///     }
+/// }
+///
+/// let mut world = World::new();
+/// let entity = world.spawn(MoverBundle {
+///     position: Position(0.0, 0.0),
+///     velocity: Velocity(1.0, 0.0),
+/// });
+/// assert!(world.has::<Position>(entity) && world.has::<Velocity>(entity));
+/// ```
+#[macro_export]
+macro_rules! bundle {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $($(#[$field_meta:meta])* $field_vis:vis $field:ident: $ty:ty),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name {
+            $($(#[$field_meta])* $field_vis $field: $ty),*
+        }
+
+        impl $crate::Bundle for $name {
+            fn table_types(types: &mut ::std::vec::Vec<::std::any::TypeId>) {
+                $(<$ty as $crate::Bundle>::table_types(types);)*
+            }
+
+            fn component_types(types: &mut ::std::vec::Vec<::std::any::TypeId>) {
+                $(<$ty as $crate::Bundle>::component_types(types);)*
+            }
+
+            fn write(self, writer: &mut $crate::BundleWriter<'_>) {
+                $($crate::Bundle::write(self.$field, writer);)*
+            }
+        }
+    };
+}
+
+/// Writes the components of a bundle to an entity that has already been moved to the archetype
+/// with all of the bundle's table components.
+pub struct BundleWriter<'w> {
+    world: &'w mut World,
+    entity: Entity,
+    location: EntityLocation,
+    tick: u32,
+}
+
+impl<'w> BundleWriter<'w> {
+    pub(crate) fn new(world: &'w mut World, entity: Entity, location: EntityLocation) -> Self {
+        let tick = world.change_tick();
+        BundleWriter {
+            world,
+            entity,
+            location,
+            tick,
+        }
+    }
+
+    /// Adds the component to the entity, replacing the one it already has.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the component is stored in a table that the bundle did not list in
+    /// `Bundle::table_types`.
+    pub fn push<C: Component>(&mut self, component: C) {
+        let (entity, location) = (self.entity, self.location);
+        if C::STORAGE == StorageType::Table {
+            assert!(
+                self.world
+                    .archetypes
+                    .get(location.archetype)
+                    .contains(TypeId::of::<C>()),
+                "{} is missing from the table types of the bundle",
+                type_name::<C>()
+            );
+        }
+
+        let component_vec = self.world.component_vec_or_insert::<C>();
+        if component_vec.ticks(entity).is_some() {
+            component_vec.replace(entity, location, component, self.tick);
+        } else {
+            component_vec.push(entity, location.archetype, component, self.tick);
+        }
+    }
+}
+
+type WriteComponent = Box<dyn FnOnce(&mut BundleWriter<'_>) + Send + Sync>;
+
+/// Collects components to spawn an entity with, for when they are not known statically, e.g.
+/// `world.build_entity().with(Position(0.0)).with(Velocity(1.0)).build()`.
+pub struct EntityBuilder<'w> {
+    world: &'w mut World,
+    table_types: Vec<TypeId>,
+    component_types: Vec<TypeId>,
+    components: Vec<WriteComponent>,
+}
+
+impl<'w> EntityBuilder<'w> {
+    pub(crate) fn new(world: &'w mut World) -> Self {
+        EntityBuilder {
+            world,
+            table_types: Vec::new(),
+            component_types: Vec::new(),
+            components: Vec::new(),
+        }
+    }
+
+    pub fn with<B: Bundle>(mut self, bundle: B) -> Self {
+        B::table_types(&mut self.table_types);
+        B::component_types(&mut self.component_types);
+        self.components
+            .push(Box::new(move |writer: &mut BundleWriter<'_>| {
+                bundle.write(writer)
+            }));
+        self
+    }
+
+    /// Spawns the entity with all of the components in one go.
+    pub fn build(mut self) -> Entity {
+        self.table_types.sort();
+        self.table_types.dedup();
+        let archetype = self.world.archetypes.get_or_insert(self.table_types);
+        let (entity, location) = self.world.alloc_entity(archetype);
+        let hooked = self.world.hooked_types(entity, self.component_types);
+
+        let mut writer = BundleWriter::new(self.world, entity, location);
+        for write in self.components {
+            write(&mut writer);
+        }
+        self.world.run_insert_hooks(entity, &hooked);
+        entity
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CommandQueue, Commands, Component, Entity, StorageType, With, Without, World};
+
+    struct Position(i32);
+    impl Component for Position {}
+    struct Velocity(i32);
+    impl Component for Velocity {}
+    struct Stunned;
+    impl Component for Stunned {
+        const STORAGE: StorageType = StorageType::SparseSet;
+    }
+
+    bundle! {
+        struct MoverBundle {
+            position: Position,
+            velocity: Velocity,
+        }
+    }
+
+    #[test]
+    fn spawn_bundles() {
+        let mut world = World::new();
+        let first = world.spawn((Position(1), Velocity(2)));
+        let second = world.spawn(MoverBundle {
+            position: Position(3),
+            velocity: Velocity(4),
+        });
+        let stunned = world.spawn(((Position(5),), Stunned));
+
+        // Both movers share one archetype, created without passing through the others
+        assert_eq!(world.archetypes().count(), 3);
+        let movers: Vec<(i32, i32)> = world
+            .query::<(&Position, &Velocity)>()
+            .iter()
+            .map(|(position, velocity)| (position.0, velocity.0))
+            .collect();
+        assert_eq!(movers, vec![(1, 2), (3, 4)]);
+        assert_eq!(
+            world
+                .query_filtered::<Entity, (With<Stunned>, Without<Velocity>)>()
+                .iter()
+                .collect::<Vec<_>>(),
+            vec![stunned]
+        );
+        assert!(world.is_alive(first) && world.is_alive(second));
+    }
+
+    #[test]
+    fn insert_bundles_into_existing_entities() {
+        let mut world = World::new();
+        let entity = world.spawn(Position(1));
+        world.insert_bundle(entity, (Velocity(2), Position(3), Stunned));
+        assert_eq!(world.get::<Position>(entity).unwrap().0, 3);
+        assert_eq!(world.get::<Velocity>(entity).unwrap().0, 2);
+        assert!(world.has::<Stunned>(entity));
+
+        world.despawn(entity);
+        assert!(world.try_insert_bundle(entity, Velocity(1)).is_err());
+    }
+
+    #[test]
+    fn spawn_batches() {
+        let mut world = World::new();
+        let recycled = world.spawn(Position(0));
+        world.despawn(recycled);
+
+        let entities = world.spawn_batch((0..1000).map(|i| (Position(i), Velocity(-i), Stunned)));
+        assert_eq!(entities.len(), 1000);
+        assert_eq!(entities[0].index(), recycled.index());
+        for (i, entity) in entities.iter().enumerate() {
+            assert_eq!(world.get::<Position>(*entity).unwrap().0, i as i32);
+            assert_eq!(world.get::<Velocity>(*entity).unwrap().0, -(i as i32));
+        }
+        assert_eq!(world.query::<(&Velocity, &Stunned)>().iter().count(), 1000);
+
+        assert!(world.spawn_batch(Vec::<Position>::new()).is_empty());
+    }
+
+    #[test]
+    fn build_entities() {
+        let mut world = World::new();
+        let entity = world
+            .build_entity()
+            .with(Position(1))
+            .with((Velocity(2), Stunned))
+            .build();
+        assert_eq!(world.get::<Position>(entity).unwrap().0, 1);
+        assert_eq!(world.get::<Velocity>(entity).unwrap().0, 2);
+        assert!(world.has::<Stunned>(entity));
+    }
+
+    #[test]
+    fn commands_spawn_bundles() {
+        let mut world = World::new();
+        let mut queue = CommandQueue::new();
+        let mut commands = Commands::new(&mut queue, &world);
+        let entity = commands.spawn_bundle((Position(1), Velocity(2)));
+        commands.insert_bundle(entity, Stunned);
+        queue.apply(&mut world);
+
+        assert_eq!(world.get::<Velocity>(entity).unwrap().0, 2);
+        assert!(world.has::<Stunned>(entity));
+    }
+}
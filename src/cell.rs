@@ -0,0 +1,244 @@
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Set while the value is mutably borrowed, the other bits count shared borrows
+const WRITING: usize = !(usize::MAX >> 1);
+
+/// A `RefCell` whose borrow flag is atomic, so it can be shared between threads. Like
+/// `RefCell`, conflicting borrows fail instead of blocking.
+pub(crate) struct AtomicRefCell<T> {
+    borrow: AtomicUsize,
+    value: UnsafeCell<T>,
+}
+
+// The borrow flag gives the same guarantees as a `RwLock`
+unsafe impl<T: Send> Send for AtomicRefCell<T> {}
+unsafe impl<T: Send + Sync> Sync for AtomicRefCell<T> {}
+
+impl<T> AtomicRefCell<T> {
+    pub fn new(value: T) -> Self {
+        AtomicRefCell {
+            borrow: AtomicUsize::new(0),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    pub fn try_borrow(&self) -> Option<Ref<'_, T>> {
+        let borrows = self.borrow.fetch_add(1, Ordering::Acquire);
+        if borrows & WRITING != 0 {
+            self.borrow.fetch_sub(1, Ordering::Release);
+            return None;
+        }
+        assert!(borrows + 1 < WRITING, "too many shared borrows");
+
+        Some(Ref {
+            value: unsafe { &*self.value.get() },
+            borrow: &self.borrow,
+        })
+    }
+
+    pub fn try_borrow_mut(&self) -> Option<RefMut<'_, T>> {
+        self.borrow
+            .compare_exchange(0, WRITING, Ordering::Acquire, Ordering::Relaxed)
+            .ok()?;
+
+        Some(RefMut {
+            value: unsafe { NonNull::new_unchecked(self.value.get()) },
+            borrow: &self.borrow,
+            marker: PhantomData,
+        })
+    }
+
+    pub fn borrow(&self) -> Ref<'_, T> {
+        self.try_borrow().expect("already mutably borrowed")
+    }
+
+    pub fn borrow_mut(&self) -> RefMut<'_, T> {
+        self.try_borrow_mut().expect("already borrowed")
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+}
+
+/// Shared borrow of a component, or of a whole component vec, that releases the borrow when
+/// dropped.
+pub struct Ref<'a, T: ?Sized> {
+    value: &'a T,
+    borrow: &'a AtomicUsize,
+}
+
+impl<'a, T: ?Sized> Ref<'a, T> {
+    pub fn map<U: ?Sized>(orig: Ref<'a, T>, f: impl FnOnce(&T) -> &U) -> Ref<'a, U> {
+        let value = f(orig.value);
+        let borrow = orig.borrow;
+        std::mem::forget(orig);
+        Ref { value, borrow }
+    }
+
+    /// Like `map`, but returns the original borrow if `f` returns `None`.
+    pub fn filter_map<U: ?Sized>(
+        orig: Ref<'a, T>,
+        f: impl FnOnce(&T) -> Option<&U>,
+    ) -> Result<Ref<'a, U>, Self> {
+        match f(orig.value) {
+            Some(value) => {
+                let borrow = orig.borrow;
+                std::mem::forget(orig);
+                Ok(Ref { value, borrow })
+            }
+            None => Err(orig),
+        }
+    }
+}
+
+impl<T: ?Sized> Deref for Ref<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value
+    }
+}
+
+impl<T: ?Sized> Drop for Ref<'_, T> {
+    fn drop(&mut self) {
+        self.borrow.fetch_sub(1, Ordering::Release);
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for Ref<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.value.fmt(f)
+    }
+}
+
+/// Mutable borrow of a component, or of a whole component vec, that releases the borrow when
+/// dropped.
+pub struct RefMut<'a, T: ?Sized> {
+    value: NonNull<T>,
+    borrow: &'a AtomicUsize,
+    marker: PhantomData<&'a mut T>,
+}
+
+// Like `&mut T`, which the guard stands in for
+unsafe impl<T: ?Sized + Send> Send for RefMut<'_, T> {}
+unsafe impl<T: ?Sized + Sync> Sync for RefMut<'_, T> {}
+
+impl<'a, T: ?Sized> RefMut<'a, T> {
+    pub fn map<U: ?Sized>(orig: RefMut<'a, T>, f: impl FnOnce(&mut T) -> &mut U) -> RefMut<'a, U> {
+        match Self::filter_map(orig, |value| Some(f(value))) {
+            Ok(mapped) => mapped,
+            Err(_) => unreachable!(),
+        }
+    }
+
+    /// Like `map`, but returns the original borrow if `f` returns `None`.
+    pub fn filter_map<U: ?Sized>(
+        orig: RefMut<'a, T>,
+        f: impl FnOnce(&mut T) -> Option<&mut U>,
+    ) -> Result<RefMut<'a, U>, Self> {
+        // The guard is forgotten rather than dropped on success, so the mapped value keeps the
+        // exclusive borrow
+        let mut value = orig.value;
+        match f(unsafe { value.as_mut() }) {
+            Some(mapped) => {
+                let borrow = orig.borrow;
+                std::mem::forget(orig);
+                Ok(RefMut {
+                    value: NonNull::from(mapped),
+                    borrow,
+                    marker: PhantomData,
+                })
+            }
+            None => Err(orig),
+        }
+    }
+}
+
+impl<T: ?Sized> Deref for RefMut<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { self.value.as_ref() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for RefMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { self.value.as_mut() }
+    }
+}
+
+impl<T: ?Sized> Drop for RefMut<'_, T> {
+    fn drop(&mut self) {
+        // Failed shared borrows may still be undoing their increment, so only the writing bit is
+        // cleared
+        self.borrow.fetch_and(!WRITING, Ordering::Release);
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for RefMut<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AtomicRefCell, Ref, RefMut};
+
+    #[test]
+    fn conflicting_borrows_fail() {
+        let cell = AtomicRefCell::new(vec![1, 2, 3]);
+        {
+            let first = cell.borrow();
+            let second = cell.borrow();
+            assert!(cell.try_borrow_mut().is_none());
+            assert_eq!(first.len() + second.len(), 6);
+        }
+        {
+            let mut values = cell.borrow_mut();
+            values.push(4);
+            assert!(cell.try_borrow().is_none());
+            assert!(cell.try_borrow_mut().is_none());
+        }
+        assert_eq!(cell.borrow().len(), 4);
+    }
+
+    #[test]
+    fn mapped_borrows_keep_the_cell_borrowed() {
+        let cell = AtomicRefCell::new(vec![1, 2, 3]);
+        let last = RefMut::filter_map(cell.borrow_mut(), |values| values.last_mut()).unwrap();
+        assert!(cell.try_borrow().is_none());
+        drop(last);
+
+        let missing = Ref::filter_map(cell.borrow(), |values| values.get(5));
+        assert!(missing.is_err());
+        let first = Ref::map(cell.borrow(), |values| &values[0]);
+        assert!(cell.try_borrow_mut().is_none());
+        assert_eq!(*first, 1);
+    }
+
+    #[test]
+    fn borrows_are_shared_between_threads() {
+        let cell = AtomicRefCell::new(0);
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for _ in 0..100 {
+                        if let Some(mut value) = cell.try_borrow_mut() {
+                            *value += 1;
+                        }
+                    }
+                });
+            }
+        });
+        assert!(*cell.borrow() > 0);
+        assert!(cell.try_borrow_mut().is_some());
+    }
+}
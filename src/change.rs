@@ -0,0 +1,147 @@
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+
+use crate::storage::ComponentTicks;
+
+/// Mutable reference to a component that marks it as changed when it is written through.
+///
+/// Queries for `&mut T` and `ComponentVecMut` hand these out, so `Changed<T>` only matches
+/// components that were actually dereferenced mutably, not every component that was visited.
+pub struct Mut<'a, T> {
+    value: &'a mut T,
+    ticks: &'a ComponentTicks,
+    last_run: u32,
+    this_run: u32,
+}
+
+impl<'a, T> Mut<'a, T> {
+    pub(crate) fn new(
+        value: &'a mut T,
+        ticks: &'a ComponentTicks,
+        last_run: u32,
+        this_run: u32,
+    ) -> Self {
+        Mut {
+            value,
+            ticks,
+            last_run,
+            this_run,
+        }
+    }
+
+    /// Whether the component was added since the system, or the query, last ran.
+    pub fn is_added(&self) -> bool {
+        self.ticks.is_added(self.last_run)
+    }
+
+    /// Whether the component was added or changed since the system, or the query, last ran.
+    pub fn is_changed(&self) -> bool {
+        self.ticks.is_changed(self.last_run)
+    }
+
+    pub fn set_changed(&mut self) {
+        self.ticks.set_changed(self.this_run);
+    }
+
+    /// Returns the component without marking it as changed, for writes that other systems
+    /// should not react to.
+    pub fn bypass_change_detection(&mut self) -> &mut T {
+        self.value
+    }
+
+    /// Marks the component as changed and returns the reference for the rest of its lifetime.
+    pub fn into_inner(mut self) -> &'a mut T {
+        self.set_changed();
+        self.value
+    }
+}
+
+impl<T> Deref for Mut<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value
+    }
+}
+
+impl<T> DerefMut for Mut<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.set_changed();
+        self.value
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Mut<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.value.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use crate::{Changed, Component, Entity, Query, Schedule, World};
+
+    struct Health(i32);
+    impl Component for Health {}
+
+    fn changed(world: &World) -> Vec<Entity> {
+        world
+            .query_filtered::<Entity, Changed<Health>>()
+            .iter()
+            .collect()
+    }
+
+    #[test]
+    fn only_written_components_are_changed() {
+        let mut world = World::new();
+        let entities = world.spawn_batch((0..4).map(Health));
+        world.clear_trackers();
+
+        for mut health in world.query::<&mut Health>().iter() {
+            assert!(!health.is_added() && !health.is_changed());
+            if health.0 % 2 == 1 {
+                health.0 += 10;
+            }
+        }
+        assert_eq!(changed(&world), vec![entities[1], entities[3]]);
+
+        world.clear_trackers();
+        for mut health in world.query::<&mut Health>().iter() {
+            health.bypass_change_detection().0 = 0;
+        }
+        {
+            let mut healths = world.borrow_component_vec_mut::<Health>().unwrap();
+            let mut health = healths.get_mut(entities[2]).unwrap();
+            health.0 = 7;
+            assert!(health.is_changed());
+        }
+        assert_eq!(changed(&world), vec![entities[2]]);
+    }
+
+    #[test]
+    fn systems_only_see_changes_since_their_last_run() {
+        let detected = Arc::new(Mutex::new(Vec::new()));
+        let recorded = detected.clone();
+        let mut schedule = Schedule::new();
+        schedule.add_stage("update").add_system_to_stage(
+            "update",
+            move |mut healths: Query<&mut Health>| {
+                for health in healths.iter() {
+                    recorded.lock().unwrap().push(health.is_changed());
+                }
+            },
+        );
+
+        let mut world = World::new();
+        let entity = world.spawn(Health(1));
+        schedule.run(&mut world);
+        schedule.run(&mut world);
+        world.get_mut::<Health>(entity).unwrap().0 = 2;
+        schedule.run(&mut world);
+        schedule.run(&mut world);
+
+        assert_eq!(*detected.lock().unwrap(), vec![true, false, true, false]);
+    }
+}
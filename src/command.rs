@@ -0,0 +1,251 @@
+use crate::entity::Entities;
+use crate::{Access, Bundle, Component, Entity, SystemParam, World};
+
+type Command = Box<dyn FnOnce(&mut World) + Send>;
+
+/// Changes to a `World` recorded by `Commands`, applied in order by `apply`.
+#[derive(Default)]
+pub struct CommandQueue {
+    commands: Vec<Command>,
+}
+
+impl CommandQueue {
+    pub fn new() -> Self {
+        CommandQueue::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// Applies the recorded commands, leaving the queue empty.
+    pub fn apply(&mut self, world: &mut World) {
+        // Entities spawned by the commands must exist before anything is inserted into them
+        world.flush_entities();
+        for command in self.commands.drain(..) {
+            command(world);
+        }
+    }
+}
+
+/// Records structural changes to a `World` that cannot be made while it is borrowed, such as
+/// spawning entities from inside a system.
+///
+/// As a system parameter, the commands are applied at the end of the stage the system runs in.
+/// Outside of systems, they are applied by `CommandQueue::apply`:
+///
+/// ```
+/// use becs::{CommandQueue, Commands, Component, World};
+///
+/// struct Projectile;
+/// impl Component for Projectile {}
+///
+/// let mut world = World::new();
+/// let mut queue = CommandQueue::new();
+/// let projectile = {
+///     let mut commands = Commands::new(&mut queue, &world);
+///     let projectile = commands.spawn();
+///     commands.insert(projectile, Projectile);
+///     projectile
+/// };
+///
+/// assert!(!world.is_alive(projectile));
+/// queue.apply(&mut world);
+/// assert!(world.has::<Projectile>(projectile));
+/// ```
+pub struct Commands<'w, 's> {
+    queue: &'s mut CommandQueue,
+    entities: &'w Entities,
+}
+
+impl<'w, 's> Commands<'w, 's> {
+    pub fn new(queue: &'s mut CommandQueue, world: &'w World) -> Self {
+        Commands {
+            queue,
+            entities: &world.entities,
+        }
+    }
+
+    /// Returns an entity that starts without components, ready for the commands that follow.
+    ///
+    /// The handle is reserved right away, and the world allocates it as an empty entity the next
+    /// time it allocates any entity, whether or not these commands are ever applied.
+    pub fn spawn(&mut self) -> Entity {
+        self.entities.reserve()
+    }
+
+    /// Returns an entity that gets the bundle when the commands are applied. Like `spawn`, it is
+    /// allocated even if they never are.
+    pub fn spawn_bundle<B: Bundle>(&mut self, bundle: B) -> Entity {
+        let entity = self.spawn();
+        self.insert_bundle(entity, bundle);
+        entity
+    }
+
+    /// Despawns the entity when the commands are applied, unless it is already dead by then.
+    pub fn despawn(&mut self, entity: Entity) {
+        self.add(move |world| {
+            world.despawn(entity);
+        });
+    }
+
+    /// Adds or replaces the component when the commands are applied, like
+    /// `World::add_component_to_entity`, unless the entity is dead by then.
+    pub fn insert<ComponentType: Component>(&mut self, entity: Entity, component: ComponentType) {
+        self.add(move |world| {
+            let _ = world.try_add_component_to_entity(entity, component);
+        });
+    }
+
+    /// Adds or replaces every component of the bundle when the commands are applied, like
+    /// `World::insert_bundle`, unless the entity is dead by then.
+    pub fn insert_bundle<B: Bundle>(&mut self, entity: Entity, bundle: B) {
+        self.add(move |world| {
+            let _ = world.try_insert_bundle(entity, bundle);
+        });
+    }
+
+    /// Removes the component, if the entity still has it, when the commands are applied.
+    pub fn remove<ComponentType: Component>(&mut self, entity: Entity) {
+        self.add(move |world| {
+            world.remove_component::<ComponentType>(entity);
+        });
+    }
+
+    /// Records an arbitrary change to the world.
+    pub fn add(&mut self, command: impl FnOnce(&mut World) + Send + 'static) {
+        self.queue.commands.push(Box::new(command));
+    }
+}
+
+// Reserving entities is atomic and everything else waits for `apply`, so commands borrow
+// nothing while systems run
+impl SystemParam for Commands<'_, '_> {
+    type State = CommandQueue;
+    type Item<'w, 's> = Commands<'w, 's>;
+
+    fn init_state(_world: &mut World, _access: &mut Access) -> Self::State {
+        CommandQueue::new()
+    }
+
+    fn get_param<'w, 's>(
+        state: &'s mut Self::State,
+        world: &'w World,
+        _last_run: u32,
+        _this_run: u32,
+    ) -> Self::Item<'w, 's> {
+        Commands::new(state, world)
+    }
+
+    fn apply(state: &mut Self::State, world: &mut World) {
+        state.apply(world);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CommandQueue, Commands, Component, Entity, Query, Schedule, World};
+
+    struct Weapon {
+        cooldown: u32,
+    }
+    impl Component for Weapon {}
+    struct Projectile(Entity);
+    impl Component for Projectile {}
+
+    #[test]
+    fn systems_spawn_while_iterating() {
+        let mut schedule = Schedule::new();
+        schedule.add_stage("update").add_system_to_stage(
+            "update",
+            |mut commands: Commands, mut weapons: Query<(Entity, &mut Weapon)>| {
+                for (entity, mut weapon) in weapons.iter() {
+                    if weapon.cooldown == 0 {
+                        let projectile = commands.spawn();
+                        commands.insert(projectile, Projectile(entity));
+                        weapon.cooldown = 2;
+                    } else {
+                        weapon.cooldown -= 1;
+                    }
+                }
+            },
+        );
+
+        let mut world = World::new();
+        let weapon = world.new_entity();
+        world.add_component_to_entity(weapon, Weapon { cooldown: 0 });
+        for _ in 0..4 {
+            schedule.run(&mut world);
+        }
+
+        let projectiles: Vec<Entity> = world
+            .query::<&Projectile>()
+            .iter()
+            .map(|projectile| projectile.0)
+            .collect();
+        assert_eq!(projectiles, vec![weapon, weapon]);
+    }
+
+    #[test]
+    fn commands_apply_in_order() {
+        let mut world = World::new();
+        let target = world.new_entity();
+        world.add_component_to_entity(target, Weapon { cooldown: 1 });
+
+        let mut queue = CommandQueue::new();
+        let mut commands = Commands::new(&mut queue, &world);
+        let spawned = commands.spawn();
+        let despawned = commands.spawn();
+        commands.insert(spawned, Projectile(target));
+        commands.remove::<Weapon>(target);
+        commands.despawn(despawned);
+        commands.add(move |world| world.add_component_to_entity(target, Projectile(spawned)));
+        assert_eq!(queue.len(), 4);
+
+        // Reserved entities are not handed out again by the world
+        let other = world.new_entity();
+        assert!(other != spawned && other != despawned);
+
+        queue.apply(&mut world);
+        assert!(queue.is_empty());
+        assert_eq!(world.get::<Projectile>(spawned).unwrap().0, target);
+        assert!(!world.is_alive(despawned));
+        assert!(!world.has::<Weapon>(target));
+        assert_eq!(world.get::<Projectile>(target).unwrap().0, spawned);
+    }
+
+    #[test]
+    fn commands_skip_entities_despawned_before_them() {
+        let mut world = World::new();
+        let target = world.new_entity();
+        let mut queue = CommandQueue::new();
+        let mut commands = Commands::new(&mut queue, &world);
+        commands.despawn(target);
+        commands.insert(target, Weapon { cooldown: 0 });
+        commands.insert_bundle(target, (Projectile(target),));
+        commands.remove::<Weapon>(target);
+        let parent = commands.spawn();
+        commands.set_parent(target, parent);
+        queue.apply(&mut world);
+
+        assert!(!world.is_alive(target));
+        assert_eq!(world.query::<&Weapon>().iter().count(), 0);
+    }
+
+    #[test]
+    fn reserved_entities_outlive_dropped_queues() {
+        let mut world = World::new();
+        let mut queue = CommandQueue::new();
+        let reserved = Commands::new(&mut queue, &world).spawn();
+        drop(queue);
+
+        let other = world.new_entity();
+        assert_ne!(other, reserved);
+        assert!(world.is_alive(reserved));
+        assert_eq!(world.query::<&Weapon>().iter().count(), 0);
+    }
+}
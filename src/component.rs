@@ -0,0 +1,33 @@
+/// How the components of a type are stored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StorageType {
+    /// In the columns of the entity's archetype. Iterating is fastest, but adding or removing
+    /// the component moves all of the entity's table components to another archetype.
+    Table,
+    /// In a sparse set keyed by entity index. Adding and removing is cheap and does not move
+    /// the entity, which suits tags and timers that come and go.
+    SparseSet,
+    /// In a `Vec<Option<T>>` indexed by entity, with a slot for every entity. Suits components
+    /// that nearly every entity has.
+    Dense,
+}
+
+/// Data that can be attached to an entity. Components must be `Send + Sync`, as systems using
+/// them may run on other threads.
+///
+/// Components are stored in archetype tables unless `STORAGE` says otherwise:
+///
+/// ```
+/// use becs::{Component, StorageType};
+///
+/// struct Position(f32, f32);
+/// impl Component for Position {}
+///
+/// struct Stunned;
+/// impl Component for Stunned {
+///     const STORAGE: StorageType = StorageType::SparseSet;
+/// }
+/// ```
+pub trait Component: Send + Sync + 'static {
+    const STORAGE: StorageType = StorageType::Table;
+}
@@ -0,0 +1,188 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::archetype::EntityLocation;
+use crate::BecsError;
+
+/// Handle to an entity in a `World`.
+///
+/// The index identifies the entity's slot in the component vecs, and the generation is bumped
+/// every time that slot is freed, so a handle to a despawned entity never matches whatever entity
+/// reuses its slot later.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Entity {
+    index: u32,
+    generation: u32,
+}
+
+impl Entity {
+    pub(crate) fn new(index: u32, generation: u32) -> Self {
+        Entity { index, generation }
+    }
+
+    pub fn index(self) -> u32 {
+        self.index
+    }
+
+    pub fn generation(self) -> u32 {
+        self.generation
+    }
+}
+
+impl fmt::Debug for Entity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}v{}", self.index, self.generation)
+    }
+}
+
+struct EntityMeta {
+    generation: u32,
+    alive: bool,
+    location: EntityLocation,
+}
+
+/// Allocator for entity handles, recycling the slots of despawned entities, which also tracks
+/// where each alive entity's components are stored.
+#[derive(Default)]
+pub(crate) struct Entities {
+    meta: Vec<EntityMeta>,
+    free: Vec<u32>,
+    // Number of slots past the end of `meta` handed out by `reserve`, which `flush` allocates
+    reserved: AtomicU32,
+}
+
+impl Entities {
+    /// Allocates an entity at `location`, reusing a freed slot if there is one. Reserved entities
+    /// must have been flushed first.
+    pub fn alloc(&mut self, location: EntityLocation) -> Entity {
+        debug_assert_eq!(
+            *self.reserved.get_mut(),
+            0,
+            "reserved entities were not flushed"
+        );
+        if let Some(index) = self.free.pop() {
+            let meta = &mut self.meta[index as usize];
+            meta.alive = true;
+            meta.location = location;
+            return Entity::new(index, meta.generation);
+        }
+
+        let index = self.meta.len() as u32;
+        self.meta.push(EntityMeta {
+            generation: 0,
+            alive: true,
+            location,
+        });
+        Entity::new(index, 0)
+    }
+
+    /// Reserves a handle through a shared reference. The entity is only allocated, without
+    /// reusing freed slots, by the next `flush`.
+    pub fn reserve(&self) -> Entity {
+        let index = self.meta.len() as u32 + self.reserved.fetch_add(1, Ordering::Relaxed);
+        Entity::new(index, 0)
+    }
+
+    /// Allocates every reserved entity at the location returned by `place`.
+    pub fn flush(&mut self, mut place: impl FnMut(Entity) -> EntityLocation) {
+        let reserved = std::mem::take(self.reserved.get_mut());
+        for _ in 0..reserved {
+            let entity = Entity::new(self.meta.len() as u32, 0);
+            let location = place(entity);
+            self.meta.push(EntityMeta {
+                generation: 0,
+                alive: true,
+                location,
+            });
+        }
+    }
+
+    /// Makes room for `additional` more entities, on top of the freed slots waiting for reuse.
+    pub fn reserve_slots(&mut self, additional: usize) {
+        self.meta
+            .reserve(additional.saturating_sub(self.free.len()));
+    }
+
+    /// Frees the entity's slot, returning `false` if the handle was already stale.
+    pub fn free(&mut self, entity: Entity) -> bool {
+        if !self.contains(entity) {
+            return false;
+        }
+
+        let meta = &mut self.meta[entity.index as usize];
+        meta.alive = false;
+        meta.generation = meta.generation.wrapping_add(1);
+        self.free.push(entity.index);
+        true
+    }
+
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.location(entity).is_some()
+    }
+
+    /// Checks that the handle refers to an alive entity, telling apart handles that were never
+    /// allocated from handles to despawned entities.
+    pub fn check(&self, entity: Entity) -> Result<EntityLocation, BecsError> {
+        match self.meta.get(entity.index as usize) {
+            None => Err(BecsError::NoSuchEntity(entity)),
+            Some(meta) if meta.alive && meta.generation == entity.generation => Ok(meta.location),
+            Some(_) => Err(BecsError::StaleEntity(entity)),
+        }
+    }
+
+    pub fn location(&self, entity: Entity) -> Option<EntityLocation> {
+        self.check(entity).ok()
+    }
+
+    /// Returns the alive entity occupying the slot at `index` and its location, if any.
+    pub fn location_at(&self, index: usize) -> Option<(Entity, EntityLocation)> {
+        let meta = self.meta.get(index)?;
+        if meta.alive {
+            Some((Entity::new(index as u32, meta.generation), meta.location))
+        } else {
+            None
+        }
+    }
+
+    pub fn set_location(&mut self, entity: Entity, location: EntityLocation) {
+        self.meta[entity.index as usize].location = location;
+    }
+
+    /// Every slot, as the handle of its current generation and whether that entity is alive.
+    pub fn slots(&self) -> impl Iterator<Item = (Entity, bool)> + '_ {
+        self.meta
+            .iter()
+            .enumerate()
+            .map(|(index, meta)| (Entity::new(index as u32, meta.generation), meta.alive))
+    }
+
+    /// Recreates the slots returned by `slots` in an allocator that has none, placing each alive
+    /// entity at the location returned by `place`, so handles keep their meaning.
+    pub fn restore(
+        &mut self,
+        slots: impl IntoIterator<Item = (Entity, bool)>,
+        mut place: impl FnMut(Entity) -> EntityLocation,
+    ) {
+        debug_assert!(self.meta.is_empty(), "entities were already allocated");
+        for (entity, alive) in slots {
+            debug_assert_eq!(entity.index as usize, self.meta.len());
+            let location = if alive {
+                place(entity)
+            } else {
+                self.free.push(entity.index);
+                EntityLocation::EMPTY
+            };
+            self.meta.push(EntityMeta {
+                generation: entity.generation,
+                alive,
+                location,
+            });
+        }
+    }
+
+    /// Number of slots ever allocated, alive or not.
+    pub fn len(&self) -> usize {
+        self.meta.len()
+    }
+}
@@ -0,0 +1,36 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::Entity;
+
+/// Error returned by the fallible `try_` methods of `World`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BecsError {
+    /// The entity's index was never allocated by this world.
+    NoSuchEntity(Entity),
+    /// The entity has been despawned, and its index may have been reused since.
+    StaleEntity(Entity),
+    /// The entity, or the whole world, has no component of the named type.
+    ComponentMissing(&'static str),
+    /// The world has no resource of the named type.
+    ResourceMissing(&'static str),
+    /// The named component vec or resource is already borrowed in a way that conflicts with the
+    /// request.
+    AlreadyBorrowed(&'static str),
+}
+
+impl fmt::Display for BecsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BecsError::NoSuchEntity(entity) => write!(f, "entity {:?} does not exist", entity),
+            BecsError::StaleEntity(entity) => write!(f, "entity {:?} has been despawned", entity),
+            BecsError::ComponentMissing(component) => {
+                write!(f, "component {} is missing", component)
+            }
+            BecsError::ResourceMissing(resource) => write!(f, "resource {} is missing", resource),
+            BecsError::AlreadyBorrowed(name) => write!(f, "{} is already borrowed", name),
+        }
+    }
+}
+
+impl Error for BecsError {}
@@ -0,0 +1,290 @@
+use std::any::TypeId;
+use std::marker::PhantomData;
+
+use crate::{Access, Res, ResMut, SystemParam, World};
+
+/// Channel of events of type `E`, stored as a resource by `World::add_event`.
+///
+/// Events are double buffered: `update` runs at the end of every frame, from
+/// `World::clear_trackers`, and drops the events sent before the previous call. Each event is
+/// therefore readable for two frames, long enough for every system that runs once per frame to
+/// see it whether it runs before or after the sender.
+///
+/// ```
+/// use becs::{EventReader, EventWriter, Schedule, World};
+///
+/// struct Damage(u32);
+///
+/// let mut schedule = Schedule::new();
+/// schedule
+///     .add_stage("update")
+///     .add_system_to_stage("update", |mut damage: EventWriter<Damage>| damage.send(Damage(3)))
+///     .add_system_to_stage("update", |mut damage: EventReader<Damage>| {
+///         assert_eq!(damage.read().map(|damage| damage.0).sum::<u32>(), 3);
+///     });
+///
+/// let mut world = World::new();
+/// world.add_event::<Damage>();
+/// schedule.run(&mut world);
+/// schedule.run(&mut world);
+/// ```
+pub struct Events<E> {
+    previous: Vec<E>,
+    current: Vec<E>,
+    // Id of the first event in `previous`, ids counting every event ever sent
+    start: usize,
+}
+
+impl<E> Default for Events<E> {
+    fn default() -> Self {
+        Events {
+            previous: Vec::new(),
+            current: Vec::new(),
+            start: 0,
+        }
+    }
+}
+
+impl<E> Events<E> {
+    pub fn send(&mut self, event: E) {
+        self.current.push(event);
+    }
+
+    /// Drops the events sent before the last update, keeping those sent since.
+    pub fn update(&mut self) {
+        self.start += self.previous.len();
+        std::mem::swap(&mut self.previous, &mut self.current);
+        self.current.clear();
+    }
+
+    /// Returns a cursor that reads every event still buffered.
+    pub fn cursor(&self) -> EventCursor<E> {
+        EventCursor::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.previous.len() + self.current.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn end(&self) -> usize {
+        self.start + self.len()
+    }
+}
+
+/// Position of a reader in an `Events<E>`, so each event is read once.
+pub struct EventCursor<E> {
+    next: usize,
+    marker: PhantomData<fn() -> E>,
+}
+
+impl<E> Default for EventCursor<E> {
+    fn default() -> Self {
+        EventCursor {
+            next: 0,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<E> EventCursor<E> {
+    /// Reads the events sent since the last read, skipping those already dropped.
+    pub fn read<'a>(&mut self, events: &'a Events<E>) -> impl Iterator<Item = &'a E> + 'a {
+        let skip = self.next.saturating_sub(events.start);
+        self.next = events.end();
+        events.previous.iter().chain(&events.current).skip(skip)
+    }
+}
+
+/// Sends events of type `E` from a system.
+///
+/// Systems taking `EventWriter<E>` panic if `E` was not added with `World::add_event`.
+pub struct EventWriter<'w, E: Send + Sync + 'static> {
+    events: ResMut<'w, Events<E>>,
+}
+
+impl<E: Send + Sync + 'static> EventWriter<'_, E> {
+    pub fn send(&mut self, event: E) {
+        self.events.send(event);
+    }
+}
+
+impl<E: Send + Sync + 'static> SystemParam for EventWriter<'_, E> {
+    type State = ();
+    type Item<'w, 's> = EventWriter<'w, E>;
+
+    fn init_state(world: &mut World, access: &mut Access) -> Self::State {
+        ResMut::<Events<E>>::init_state(world, access)
+    }
+
+    fn get_param<'w, 's>(
+        state: &'s mut Self::State,
+        world: &'w World,
+        last_run: u32,
+        this_run: u32,
+    ) -> Self::Item<'w, 's> {
+        EventWriter {
+            events: ResMut::get_param(state, world, last_run, this_run),
+        }
+    }
+}
+
+/// Reads events of type `E` from a system. Each system has a cursor of its own, so it sees
+/// every event once.
+///
+/// Systems taking `EventReader<E>` panic if `E` was not added with `World::add_event`.
+pub struct EventReader<'w, 's, E: Send + Sync + 'static> {
+    events: Res<'w, Events<E>>,
+    cursor: &'s mut EventCursor<E>,
+}
+
+impl<E: Send + Sync + 'static> EventReader<'_, '_, E> {
+    pub fn read(&mut self) -> impl Iterator<Item = &E> {
+        self.cursor.read(&self.events)
+    }
+}
+
+impl<E: Send + Sync + 'static> SystemParam for EventReader<'_, '_, E> {
+    type State = EventCursor<E>;
+    type Item<'w, 's> = EventReader<'w, 's, E>;
+
+    fn init_state(world: &mut World, access: &mut Access) -> Self::State {
+        Res::<Events<E>>::init_state(world, access);
+        EventCursor::default()
+    }
+
+    fn get_param<'w, 's>(
+        state: &'s mut Self::State,
+        world: &'w World,
+        last_run: u32,
+        this_run: u32,
+    ) -> Self::Item<'w, 's> {
+        EventReader {
+            events: Res::get_param(&mut (), world, last_run, this_run),
+            cursor: state,
+        }
+    }
+}
+
+impl World {
+    /// Adds the `Events<E>` resource, updated at the end of every frame, unless it exists already.
+    pub fn add_event<E: Send + Sync + 'static>(&mut self) {
+        if self.contains_resource::<Events<E>>() {
+            return;
+        }
+        self.insert_resource(Events::<E>::default());
+        self.event_updates.insert(TypeId::of::<E>(), |world| {
+            if let Some(mut events) = world.resource_mut::<Events<E>>() {
+                events.update();
+            }
+        });
+    }
+
+    /// Sends an event through `Events<E>`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `E` was not added with `add_event`.
+    pub fn send_event<E: Send + Sync + 'static>(&mut self, event: E) {
+        self.resource_mut::<Events<E>>()
+            .unwrap_or_else(|| panic!("event {} was not added", std::any::type_name::<E>()))
+            .send(event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use crate::{Entity, EventReader, EventWriter, Events, Query, Schedule, World};
+
+    struct Collision(u32);
+    struct Damage(u32);
+
+    #[test]
+    fn events_live_for_two_updates() {
+        let mut events = Events::default();
+        let mut early = events.cursor();
+        let mut late = events.cursor();
+        events.send(Collision(1));
+        events.send(Collision(2));
+        assert_eq!(
+            early.read(&events).map(|c| c.0).collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+
+        events.update();
+        events.send(Collision(3));
+        assert_eq!(
+            early.read(&events).map(|c| c.0).collect::<Vec<_>>(),
+            vec![3]
+        );
+        assert_eq!(early.read(&events).count(), 0);
+
+        events.update();
+        events.send(Collision(4));
+        // The first two events were dropped before this cursor got to them
+        assert_eq!(
+            late.read(&events).map(|c| c.0).collect::<Vec<_>>(),
+            vec![3, 4]
+        );
+        events.update();
+        events.update();
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn systems_exchange_events() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let recorded = received.clone();
+        let mut schedule = Schedule::new();
+        schedule
+            .add_stage("update")
+            .add_system_to_stage("update", move |mut damage: EventReader<Damage>| {
+                // Runs before the sender, so it sees last frame's events
+                recorded
+                    .lock()
+                    .unwrap()
+                    .extend(damage.read().map(|damage| damage.0));
+            })
+            .add_system_to_stage(
+                "update",
+                |mut collisions: EventReader<Collision>,
+                 mut damage: EventWriter<Damage>,
+                 mut entities: Query<Entity>| {
+                    for collision in collisions.read() {
+                        damage.send(Damage(collision.0 * entities.iter().count() as u32));
+                    }
+                },
+            );
+
+        let mut world = World::new();
+        world.add_event::<Collision>();
+        world.add_event::<Damage>();
+        world.new_entity();
+        world.send_event(Collision(2));
+        world.send_event(Collision(5));
+        schedule.run(&mut world);
+        assert!(received.lock().unwrap().is_empty());
+        schedule.run(&mut world);
+        schedule.run(&mut world);
+        assert_eq!(*received.lock().unwrap(), vec![2, 5]);
+        assert!(world.resource::<Events<Damage>>().unwrap().is_empty());
+    }
+
+    #[test]
+    fn events_added_again_update_once_per_frame() {
+        let mut world = World::new();
+        world.add_event::<Collision>();
+        world.remove_resource::<Events<Collision>>();
+        world.add_event::<Collision>();
+        world.send_event(Collision(1));
+        world.clear_trackers();
+        assert_eq!(world.resource::<Events<Collision>>().unwrap().len(), 1);
+        world.clear_trackers();
+        assert!(world.resource::<Events<Collision>>().unwrap().is_empty());
+    }
+}
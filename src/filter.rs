@@ -0,0 +1,321 @@
+use std::any::TypeId;
+use std::marker::PhantomData;
+
+use crate::access::Access;
+use crate::archetype::{Archetype, EntityLocation};
+use crate::storage::ComponentTicks;
+use crate::{Component, Entity, StorageType, World};
+
+/// Constrains which entities a query matches without fetching any data, e.g.
+/// `world.query_filtered::<&Health, (With<Enemy>, Without<Dead>)>()`.
+///
+/// Tuples of filters match when all of their filters match.
+pub trait QueryFilter {
+    type Fetch<'w>;
+
+    /// Borrows whatever the filter needs to inspect. Change filters match components added or
+    /// changed after `last_run`.
+    fn init_fetch(world: &World, last_run: u32) -> Self::Fetch<'_>;
+
+    /// Whether entities of the archetype can match the filter, so whole archetypes can be
+    /// skipped.
+    fn matches_archetype(archetype: &Archetype) -> bool;
+
+    /// Adds the component types whose ticks the filter reads. Filters on the presence of
+    /// components read nothing, as it only changes through `&mut World`.
+    fn update_access(_access: &mut Access) {}
+
+    /// Whether the entity at `location` matches. Only called for entities of archetypes that
+    /// passed `matches_archetype`.
+    fn matches(
+        fetch: &Self::Fetch<'_>,
+        archetype: &Archetype,
+        entity: Entity,
+        location: EntityLocation,
+    ) -> bool;
+}
+
+impl QueryFilter for () {
+    type Fetch<'w> = ();
+
+    fn init_fetch(_world: &World, _last_run: u32) -> Self::Fetch<'_> {}
+
+    fn matches_archetype(_archetype: &Archetype) -> bool {
+        true
+    }
+
+    fn matches(
+        _fetch: &Self::Fetch<'_>,
+        _archetype: &Archetype,
+        _entity: Entity,
+        _location: EntityLocation,
+    ) -> bool {
+        true
+    }
+}
+
+fn is_table<T: Component>() -> bool {
+    T::STORAGE == StorageType::Table
+}
+
+/// Fetch shared by the filters. Ticks are read without borrowing the components, so a query can
+/// filter on `Changed<T>` while fetching `&mut T`. Entities without the component have no ticks.
+pub struct TicksFetch<'w> {
+    ticks: Option<&'w [Option<ComponentTicks>]>,
+    last_run: u32,
+}
+
+impl TicksFetch<'_> {
+    fn new<T: Component>(world: &World, last_run: u32) -> TicksFetch<'_> {
+        TicksFetch {
+            ticks: world
+                .component_vec::<T>()
+                .map(|component_vec| &component_vec.ticks[..]),
+            last_run,
+        }
+    }
+
+    fn get(&self, entity: Entity) -> Option<&ComponentTicks> {
+        self.ticks?.get(entity.index() as usize)?.as_ref()
+    }
+}
+
+/// Matches entities that have a `T` component.
+pub struct With<T>(PhantomData<T>);
+
+impl<T: Component> QueryFilter for With<T> {
+    type Fetch<'w> = TicksFetch<'w>;
+
+    fn init_fetch(world: &World, last_run: u32) -> Self::Fetch<'_> {
+        TicksFetch::new::<T>(world, last_run)
+    }
+
+    fn matches_archetype(archetype: &Archetype) -> bool {
+        !is_table::<T>() || archetype.contains(TypeId::of::<T>())
+    }
+
+    fn matches(
+        fetch: &Self::Fetch<'_>,
+        _archetype: &Archetype,
+        entity: Entity,
+        _location: EntityLocation,
+    ) -> bool {
+        is_table::<T>() || fetch.get(entity).is_some()
+    }
+}
+
+/// Matches entities that do not have a `T` component.
+pub struct Without<T>(PhantomData<T>);
+
+impl<T: Component> QueryFilter for Without<T> {
+    type Fetch<'w> = TicksFetch<'w>;
+
+    fn init_fetch(world: &World, last_run: u32) -> Self::Fetch<'_> {
+        TicksFetch::new::<T>(world, last_run)
+    }
+
+    fn matches_archetype(archetype: &Archetype) -> bool {
+        !is_table::<T>() || !archetype.contains(TypeId::of::<T>())
+    }
+
+    fn matches(
+        fetch: &Self::Fetch<'_>,
+        _archetype: &Archetype,
+        entity: Entity,
+        _location: EntityLocation,
+    ) -> bool {
+        is_table::<T>() || fetch.get(entity).is_none()
+    }
+}
+
+/// Matches entities whose `T` component was added since the query last ran.
+pub struct Added<T>(PhantomData<T>);
+
+impl<T: Component> QueryFilter for Added<T> {
+    type Fetch<'w> = TicksFetch<'w>;
+
+    fn init_fetch(world: &World, last_run: u32) -> Self::Fetch<'_> {
+        TicksFetch::new::<T>(world, last_run)
+    }
+
+    fn matches_archetype(archetype: &Archetype) -> bool {
+        !is_table::<T>() || archetype.contains(TypeId::of::<T>())
+    }
+
+    fn update_access(access: &mut Access) {
+        access.add_read::<T>();
+    }
+
+    fn matches(
+        fetch: &Self::Fetch<'_>,
+        _archetype: &Archetype,
+        entity: Entity,
+        _location: EntityLocation,
+    ) -> bool {
+        fetch
+            .get(entity)
+            .is_some_and(|ticks| ticks.is_added(fetch.last_run))
+    }
+}
+
+/// Matches entities whose `T` component was added or written to since the query last ran.
+pub struct Changed<T>(PhantomData<T>);
+
+impl<T: Component> QueryFilter for Changed<T> {
+    type Fetch<'w> = TicksFetch<'w>;
+
+    fn init_fetch(world: &World, last_run: u32) -> Self::Fetch<'_> {
+        TicksFetch::new::<T>(world, last_run)
+    }
+
+    fn matches_archetype(archetype: &Archetype) -> bool {
+        !is_table::<T>() || archetype.contains(TypeId::of::<T>())
+    }
+
+    fn update_access(access: &mut Access) {
+        access.add_read::<T>();
+    }
+
+    fn matches(
+        fetch: &Self::Fetch<'_>,
+        _archetype: &Archetype,
+        entity: Entity,
+        _location: EntityLocation,
+    ) -> bool {
+        fetch
+            .get(entity)
+            .is_some_and(|ticks| ticks.is_changed(fetch.last_run))
+    }
+}
+
+/// Matches entities that match any of the filters in the tuple `T`.
+pub struct Or<T>(PhantomData<T>);
+
+macro_rules! impl_query_filter_tuple {
+    ($($name:ident),*) => {
+        #[allow(non_snake_case)]
+        impl<$($name: QueryFilter),*> QueryFilter for ($($name,)*) {
+            type Fetch<'w> = ($($name::Fetch<'w>,)*);
+
+            fn init_fetch(world: &World, last_run: u32) -> Self::Fetch<'_> {
+                ($($name::init_fetch(world, last_run),)*)
+            }
+
+            fn matches_archetype(archetype: &Archetype) -> bool {
+                $($name::matches_archetype(archetype))&&*
+            }
+
+            fn update_access(access: &mut Access) {
+                $($name::update_access(access);)*
+            }
+
+            fn matches(
+                fetch: &Self::Fetch<'_>,
+                archetype: &Archetype,
+                entity: Entity,
+                location: EntityLocation,
+            ) -> bool {
+                let ($($name,)*) = fetch;
+                $($name::matches($name, archetype, entity, location))&&*
+            }
+        }
+
+        #[allow(non_snake_case)]
+        impl<$($name: QueryFilter),*> QueryFilter for Or<($($name,)*)> {
+            type Fetch<'w> = ($($name::Fetch<'w>,)*);
+
+            fn init_fetch(world: &World, last_run: u32) -> Self::Fetch<'_> {
+                ($($name::init_fetch(world, last_run),)*)
+            }
+
+            fn matches_archetype(archetype: &Archetype) -> bool {
+                $($name::matches_archetype(archetype))||*
+            }
+
+            fn update_access(access: &mut Access) {
+                $($name::update_access(access);)*
+            }
+
+            // Only some of the filters may have matched the archetype, so each is checked again
+            fn matches(
+                fetch: &Self::Fetch<'_>,
+                archetype: &Archetype,
+                entity: Entity,
+                location: EntityLocation,
+            ) -> bool {
+                let ($($name,)*) = fetch;
+                $(($name::matches_archetype(archetype)
+                    && $name::matches($name, archetype, entity, location)))||*
+            }
+        }
+    };
+}
+
+impl_query_filter_tuple!(A);
+impl_query_filter_tuple!(A, B);
+impl_query_filter_tuple!(A, B, C);
+impl_query_filter_tuple!(A, B, C, D);
+impl_query_filter_tuple!(A, B, C, D, E);
+impl_query_filter_tuple!(A, B, C, D, E, F);
+impl_query_filter_tuple!(A, B, C, D, E, F, G);
+impl_query_filter_tuple!(A, B, C, D, E, F, G, H);
+
+#[cfg(test)]
+mod tests {
+    use crate::{Added, Changed, Component, Entity, Or, With, Without, World};
+
+    struct Enemy;
+    impl Component for Enemy {}
+    struct Dead;
+    impl Component for Dead {}
+    struct Health(i32);
+    impl Component for Health {}
+
+    fn entities<F: crate::QueryFilter>(world: &World) -> Vec<Entity> {
+        world.query_filtered::<Entity, F>().iter().collect()
+    }
+
+    #[test]
+    fn with_and_without() {
+        let mut world = World::new();
+        let alive = world.new_entity();
+        world.add_component_to_entity(alive, Enemy);
+        let dead = world.new_entity();
+        world.add_component_to_entity(dead, Enemy);
+        world.add_component_to_entity(dead, Dead);
+        let bystander = world.new_entity();
+        world.add_component_to_entity(bystander, Health(1));
+
+        assert_eq!(
+            entities::<(With<Enemy>, Without<Dead>)>(&world),
+            vec![alive]
+        );
+        assert_eq!(entities::<Without<Enemy>>(&world), vec![bystander]);
+        assert_eq!(
+            entities::<Or<(With<Dead>, With<Health>)>>(&world),
+            vec![dead, bystander]
+        );
+    }
+
+    #[test]
+    fn added_and_changed() {
+        let mut world = World::new();
+        let first = world.new_entity();
+        world.add_component_to_entity(first, Health(1));
+        let second = world.new_entity();
+        world.add_component_to_entity(second, Health(2));
+
+        assert_eq!(entities::<Added<Health>>(&world), vec![first, second]);
+        world.clear_trackers();
+        assert!(entities::<Added<Health>>(&world).is_empty());
+        assert!(entities::<Changed<Health>>(&world).is_empty());
+
+        world.query::<&mut Health>().get(second).unwrap().0 = 3;
+        assert!(entities::<Added<Health>>(&world).is_empty());
+        assert_eq!(entities::<Changed<Health>>(&world), vec![second]);
+
+        world.clear_trackers();
+        let mut changed = world.query_filtered::<&mut Health, Changed<Health>>();
+        assert_eq!(changed.iter().count(), 0);
+    }
+}
@@ -0,0 +1,294 @@
+#[cfg(feature = "serde")]
+use std::collections::BTreeMap;
+use std::ops::Deref;
+
+use crate::cell::Ref;
+use crate::{Commands, Component, Entity, World};
+
+/// Parent of an entity in the hierarchy, set with `World::set_parent`.
+///
+/// The parent's `Children` are kept in sync by component hooks, so removing the `Parent`, or
+/// despawning the child, also takes the child out of its parent's `Children`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Parent(Entity);
+
+impl Parent {
+    pub fn get(&self) -> Entity {
+        self.0
+    }
+}
+
+impl Component for Parent {}
+
+/// Children of an entity, in the order they were given their parent.
+///
+/// Removing the `Children`, or despawning the parent without `World::despawn_recursive`, leaves
+/// the children without a parent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Children(Vec<Entity>);
+
+impl Deref for Children {
+    type Target = [Entity];
+
+    fn deref(&self) -> &[Entity] {
+        &self.0
+    }
+}
+
+impl Component for Children {}
+
+// The hooks only touch the other side of the relationship when it still points back, so they
+// can run in any order while both sides are taken apart
+fn add_child(world: &mut World, child: Entity) {
+    let parent = world.get::<Parent>(child).unwrap().0;
+    if let Some(mut children) = world.get_mut::<Children>(parent) {
+        children.0.push(child);
+        return;
+    }
+    world.add_component_to_entity(parent, Children(vec![child]));
+}
+
+fn remove_child(world: &mut World, child: Entity) {
+    let parent = world.get::<Parent>(child).unwrap().0;
+    if let Some(mut children) = world.get_mut::<Children>(parent) {
+        children.0.retain(|&other| other != child);
+    }
+}
+
+fn orphan_children(world: &mut World, parent: Entity) {
+    let children = world.get::<Children>(parent).unwrap().0.clone();
+    for child in children {
+        if world.parent(child) == Some(parent) {
+            world.remove_component::<Parent>(child);
+        }
+    }
+}
+
+// Installed in every world, so the hierarchy stays consistent however its components change
+pub(crate) fn register_hooks(world: &mut World) {
+    world
+        .register_builtin_hooks::<Parent>()
+        .on_insert(add_child)
+        .on_remove(remove_child);
+    world
+        .register_builtin_hooks::<Children>()
+        .on_remove(orphan_children);
+}
+
+// Rebuilds every `Children` from the `Parent`s, for worlds whose components were inserted
+// without hooks. Children listed in a `Children` that still point back keep their order, ahead
+// of those that were missing from it.
+#[cfg(feature = "serde")]
+pub(crate) fn rebuild_children(world: &mut World) {
+    let mut children: BTreeMap<Entity, Vec<Entity>> = BTreeMap::new();
+    let listed: Vec<(Entity, Vec<Entity>)> = world
+        .query::<(Entity, &Children)>()
+        .iter()
+        .map(|(parent, children)| (parent, children.0.clone()))
+        .collect();
+    for (parent, listed) in listed {
+        world.remove_component::<Children>(parent);
+        let entry = children.entry(parent).or_default();
+        for child in listed {
+            if world.parent(child) == Some(parent) && !entry.contains(&child) {
+                entry.push(child);
+            }
+        }
+    }
+
+    let parents: Vec<(Entity, Entity)> = world
+        .query::<(Entity, &Parent)>()
+        .iter()
+        .map(|(child, parent)| (child, parent.0))
+        .collect();
+    for (child, parent) in parents {
+        if !world.is_alive(parent) || parent == child {
+            world.remove_component::<Parent>(child);
+            continue;
+        }
+        let entry = children.entry(parent).or_default();
+        if !entry.contains(&child) {
+            entry.push(child);
+        }
+    }
+
+    for (parent, children) in children {
+        if !children.is_empty() {
+            world.add_component_to_entity(parent, Children(children));
+        }
+    }
+}
+
+impl World {
+    /// Makes `parent` the parent of `child`, taking it out of the children of its previous
+    /// parent.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either entity is dead, or if `child` is `parent` or one of its ancestors.
+    pub fn set_parent(&mut self, child: Entity, parent: Entity) {
+        assert!(self.is_alive(parent), "parent {:?} is not alive", parent);
+        let mut ancestor = Some(parent);
+        while let Some(entity) = ancestor {
+            assert!(
+                entity != child,
+                "{:?} cannot be a descendant of itself",
+                child
+            );
+            ancestor = self.parent(entity);
+        }
+
+        self.add_component_to_entity(child, Parent(parent));
+    }
+
+    /// Takes the entity out of the hierarchy above it, returning its former parent.
+    pub fn remove_parent(&mut self, child: Entity) -> Option<Entity> {
+        self.remove_component::<Parent>(child)
+            .map(|parent| parent.0)
+    }
+
+    pub fn parent(&self, entity: Entity) -> Option<Entity> {
+        self.get::<Parent>(entity).map(|parent| parent.0)
+    }
+
+    /// The children of the entity, or `None` if it has no `Children`.
+    pub fn children(&self, entity: Entity) -> Option<Ref<'_, [Entity]>> {
+        self.get::<Children>(entity)
+            .map(|children| Ref::map(children, |children| &children.0[..]))
+    }
+
+    /// Despawns the entity along with all of its descendants, returning whether it was alive.
+    pub fn despawn_recursive(&mut self, entity: Entity) -> bool {
+        let children = self
+            .children(entity)
+            .map_or_else(Vec::new, |children| children.to_vec());
+        for child in children {
+            self.despawn_recursive(child);
+        }
+        self.despawn(entity)
+    }
+}
+
+impl Commands<'_, '_> {
+    /// Makes `parent` the parent of `child` when the commands are applied, like
+    /// `World::set_parent`, unless either entity is dead by then.
+    pub fn set_parent(&mut self, child: Entity, parent: Entity) {
+        self.add(move |world| {
+            if world.is_alive(child) && world.is_alive(parent) {
+                world.set_parent(child, parent);
+            }
+        });
+    }
+
+    /// Despawns the entity and its descendants when the commands are applied.
+    pub fn despawn_recursive(&mut self, entity: Entity) {
+        self.add(move |world| {
+            world.despawn_recursive(entity);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CommandQueue, Commands, Entity, Parent, World};
+
+    fn children(world: &World, entity: Entity) -> Vec<Entity> {
+        world
+            .children(entity)
+            .map_or_else(Vec::new, |children| children.to_vec())
+    }
+
+    #[test]
+    fn both_sides_stay_in_sync() {
+        let mut world = World::new();
+        let root = world.new_entity();
+        let other = world.new_entity();
+        let [a, b, c] = [(); 3].map(|_| world.new_entity());
+        world.set_parent(a, root);
+        world.set_parent(b, root);
+        world.set_parent(c, root);
+        assert_eq!(children(&world, root), vec![a, b, c]);
+        assert_eq!(world.parent(b), Some(root));
+
+        world.set_parent(b, other);
+        assert_eq!(children(&world, root), vec![a, c]);
+        assert_eq!(children(&world, other), vec![b]);
+
+        assert_eq!(world.remove_parent(a), Some(root));
+        world.despawn(c);
+        assert!(children(&world, root).is_empty());
+
+        // Despawning a parent on its own leaves its children at the top of the hierarchy
+        world.despawn(other);
+        assert_eq!(world.parent(b), None);
+        assert!(world.is_alive(b));
+    }
+
+    #[test]
+    fn despawn_recursive_takes_every_descendant() {
+        let mut world = World::new();
+        let [root, child, grandchild, sibling] = [(); 4].map(|_| world.new_entity());
+        let parent = world.new_entity();
+        world.set_parent(root, parent);
+        world.set_parent(child, root);
+        world.set_parent(grandchild, child);
+        world.set_parent(sibling, parent);
+
+        assert!(world.despawn_recursive(root));
+        assert!(!world.is_alive(child) && !world.is_alive(grandchild));
+        assert_eq!(children(&world, parent), vec![sibling]);
+        assert!(!world.despawn_recursive(root));
+    }
+
+    #[test]
+    fn commands_build_hierarchies() {
+        let mut world = World::new();
+        let mut queue = CommandQueue::new();
+        let mut commands = Commands::new(&mut queue, &world);
+        let parent = commands.spawn();
+        let child = commands.spawn();
+        commands.set_parent(child, parent);
+        queue.apply(&mut world);
+        assert_eq!(children(&world, parent), vec![child]);
+        assert_eq!(world.get::<Parent>(child).unwrap().get(), parent);
+
+        let mut commands = Commands::new(&mut queue, &world);
+        commands.despawn_recursive(parent);
+        queue.apply(&mut world);
+        assert!(!world.is_alive(child));
+    }
+
+    #[test]
+    fn user_hooks_run_alongside_the_hierarchy() {
+        struct Reparented(Vec<Entity>);
+
+        let mut world = World::new();
+        world.insert_resource(Reparented(Vec::new()));
+        let [parent, child] = [(); 2].map(|_| world.new_entity());
+        world.set_parent(child, parent);
+        world
+            .register_component_hooks::<Parent>()
+            .on_insert(|world, child| {
+                world.resource_mut::<Reparented>().unwrap().0.push(child);
+            });
+
+        let other = world.new_entity();
+        world.set_parent(child, other);
+        assert_eq!(world.resource::<Reparented>().unwrap().0, vec![child]);
+        assert_eq!(children(&world, other), vec![child]);
+        world.despawn(child);
+        assert!(children(&world, other).is_empty());
+        assert!(children(&world, parent).is_empty());
+    }
+
+    #[test]
+    #[should_panic]
+    fn cycles_panic() {
+        let mut world = World::new();
+        let [a, b] = [(); 2].map(|_| world.new_entity());
+        world.set_parent(a, b);
+        world.set_parent(b, a);
+    }
+}
@@ -0,0 +1,326 @@
+use std::any::TypeId;
+
+use crate::{Entity, World};
+
+/// Callback run by the world when a component of a given type is added, inserted or removed.
+pub type ComponentHook = fn(&mut World, Entity);
+
+/// Hooks of a component type, registered with `World::register_component_hooks`.
+///
+/// They run for changes made through `World` methods such as `add_component_to_entity`,
+/// `insert_bundle`, `remove_component` and `despawn`, including those applied from `Commands`.
+/// The hooks the crate itself relies on, such as those keeping `Parent` and `Children` in sync,
+/// are kept apart: they run before the ones set here and are never replaced by them.
+///
+/// Hooks get the whole world, so they can keep indexes in resources up to date or clean up
+/// what the component owns:
+///
+/// ```
+/// use std::collections::HashMap;
+///
+/// use becs::{Component, Entity, World};
+///
+/// struct Name(&'static str);
+/// impl Component for Name {}
+///
+/// #[derive(Default)]
+/// struct NameIndex(HashMap<&'static str, Entity>);
+///
+/// let mut world = World::new();
+/// world.insert_resource(NameIndex::default());
+/// world
+///     .register_component_hooks::<Name>()
+///     .on_insert(|world, entity| {
+///         let name = world.get::<Name>(entity).unwrap().0;
+///         world.resource_mut::<NameIndex>().unwrap().0.insert(name, entity);
+///     })
+///     .on_remove(|world, entity| {
+///         let name = world.get::<Name>(entity).unwrap().0;
+///         world.resource_mut::<NameIndex>().unwrap().0.remove(name);
+///     });
+///
+/// let entity = world.spawn(Name("alice"));
+/// world.add_component_to_entity(entity, Name("bob"));
+/// let index = world.resource::<NameIndex>().unwrap();
+/// assert_eq!(index.0.get("bob"), Some(&entity));
+/// assert!(!index.0.contains_key("alice"));
+/// ```
+#[derive(Clone, Copy, Default)]
+pub struct ComponentHooks {
+    on_add: Option<ComponentHook>,
+    on_insert: Option<ComponentHook>,
+    on_remove: Option<ComponentHook>,
+}
+
+impl ComponentHooks {
+    /// Sets the hook run after the component is added to an entity that did not have one.
+    pub fn on_add(&mut self, hook: ComponentHook) -> &mut Self {
+        self.on_add = Some(hook);
+        self
+    }
+
+    /// Sets the hook run after the component is added or replaced, after `on_add`.
+    pub fn on_insert(&mut self, hook: ComponentHook) -> &mut Self {
+        self.on_insert = Some(hook);
+        self
+    }
+
+    /// Sets the hook run before the component leaves an entity, while it can still be read:
+    /// when it is removed, when the entity is despawned and when it is about to be replaced.
+    pub fn on_remove(&mut self, hook: ComponentHook) -> &mut Self {
+        self.on_remove = Some(hook);
+        self
+    }
+}
+
+impl World {
+    /// Returns the hooks of the component type, to set them.
+    pub fn register_component_hooks<ComponentType: crate::Component>(
+        &mut self,
+    ) -> &mut ComponentHooks {
+        self.hooks.entry(TypeId::of::<ComponentType>()).or_default()
+    }
+
+    // Hooks the crate depends on for consistency, run before and independently of those
+    // registered by users
+    pub(crate) fn register_builtin_hooks<ComponentType: crate::Component>(
+        &mut self,
+    ) -> &mut ComponentHooks {
+        self.builtin_hooks
+            .entry(TypeId::of::<ComponentType>())
+            .or_default()
+    }
+
+    // Component types with hooks among `types`, each with whether the entity already has one
+    pub(crate) fn hooked_types(
+        &self,
+        entity: Entity,
+        types: impl IntoIterator<Item = TypeId>,
+    ) -> Vec<(TypeId, bool)> {
+        let mut hooked: Vec<(TypeId, bool)> = Vec::new();
+        if self.hooks.is_empty() && self.builtin_hooks.is_empty() {
+            return hooked;
+        }
+        for type_id in types {
+            let has_hooks =
+                self.hooks.contains_key(&type_id) || self.builtin_hooks.contains_key(&type_id);
+            if has_hooks && hooked.iter().all(|&(t, _)| t != type_id) {
+                hooked.push((type_id, self.has_type(entity, type_id)));
+            }
+        }
+        hooked
+    }
+
+    pub(crate) fn run_remove_hooks(&mut self, entity: Entity, hooked: &[(TypeId, bool)]) {
+        for &(type_id, had) in hooked {
+            if had {
+                self.run_hook(type_id, entity, |hooks| hooks.on_remove);
+            }
+        }
+    }
+
+    pub(crate) fn run_insert_hooks(&mut self, entity: Entity, hooked: &[(TypeId, bool)]) {
+        for &(type_id, had) in hooked {
+            if !had {
+                self.run_hook(type_id, entity, |hooks| hooks.on_add);
+            }
+        }
+        for &(type_id, _) in hooked {
+            self.run_hook(type_id, entity, |hooks| hooks.on_insert);
+        }
+    }
+
+    fn run_hook(
+        &mut self,
+        type_id: TypeId,
+        entity: Entity,
+        hook: fn(&ComponentHooks) -> Option<ComponentHook>,
+    ) {
+        for builtin in [true, false] {
+            // Earlier hooks may have despawned the entity, or taken the component away
+            if !self.is_alive(entity) || !self.has_type(entity, type_id) {
+                return;
+            }
+            let hooks = if builtin {
+                &self.builtin_hooks
+            } else {
+                &self.hooks
+            };
+            if let Some(hook) = hooks.get(&type_id).and_then(hook) {
+                hook(self, entity);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CommandQueue, Commands, Component, Entity, StorageType, World};
+
+    #[derive(Default)]
+    struct Log(Vec<String>);
+
+    struct Handle(u32);
+    impl Component for Handle {}
+    struct Marker;
+    impl Component for Marker {
+        const STORAGE: StorageType = StorageType::SparseSet;
+    }
+
+    fn log(world: &mut World, event: String) {
+        world.resource_mut::<Log>().unwrap().0.push(event);
+    }
+
+    fn world_with_hooks() -> World {
+        let mut world = World::new();
+        world.insert_resource(Log::default());
+        world
+            .register_component_hooks::<Handle>()
+            .on_add(|world, _| log(world, "add".to_string()))
+            .on_insert(|world, entity| {
+                let handle = world.get::<Handle>(entity).unwrap().0;
+                log(world, format!("insert {}", handle));
+            })
+            .on_remove(|world, entity| {
+                let handle = world.get::<Handle>(entity).unwrap().0;
+                log(world, format!("remove {}", handle));
+            });
+        world
+            .register_component_hooks::<Marker>()
+            .on_add(|world, _| log(world, "add marker".to_string()));
+        world
+    }
+
+    fn take_log(world: &mut World) -> Vec<String> {
+        std::mem::take(&mut world.resource_mut::<Log>().unwrap().0)
+    }
+
+    #[test]
+    fn hooks_run_for_world_changes() {
+        let mut world = world_with_hooks();
+        let entity = world.new_entity();
+        world.add_component_to_entity(entity, Handle(1));
+        world.add_component_to_entity(entity, Handle(2));
+        assert_eq!(
+            take_log(&mut world),
+            vec!["add", "insert 1", "remove 1", "insert 2"]
+        );
+
+        world.insert_bundle(entity, (Marker, Handle(3)));
+        assert_eq!(
+            take_log(&mut world),
+            vec!["remove 2", "add marker", "insert 3"]
+        );
+
+        assert_eq!(world.remove_component::<Handle>(entity).unwrap().0, 3);
+        world.remove_component::<Handle>(entity);
+        assert_eq!(take_log(&mut world), vec!["remove 3"]);
+
+        let spawned = world
+            .build_entity()
+            .with(Handle(4))
+            .with((Marker, Handle(5)))
+            .build();
+        world.despawn(spawned);
+        assert_eq!(
+            take_log(&mut world),
+            vec!["add", "add marker", "insert 5", "remove 5"]
+        );
+    }
+
+    #[test]
+    fn hooks_run_for_spawns_and_commands() {
+        let mut world = world_with_hooks();
+        world.spawn_batch((0..2).map(Handle));
+        assert_eq!(
+            take_log(&mut world),
+            vec!["add", "insert 0", "add", "insert 1"]
+        );
+
+        let mut queue = CommandQueue::new();
+        let mut commands = Commands::new(&mut queue, &world);
+        let entity = commands.spawn_bundle(Handle(7));
+        commands.despawn(entity);
+        queue.apply(&mut world);
+        assert_eq!(take_log(&mut world), vec!["add", "insert 7", "remove 7"]);
+    }
+
+    #[test]
+    fn hooks_may_despawn_the_entity() {
+        let mut world = world_with_hooks();
+        world
+            .register_component_hooks::<Marker>()
+            .on_add(|world, entity| {
+                world.despawn(entity);
+            });
+        let entity: Entity = world.spawn((Marker, Handle(1)));
+        assert!(!world.is_alive(entity));
+        assert_eq!(take_log(&mut world), vec!["remove 1"]);
+    }
+
+    #[test]
+    fn hooks_may_remove_other_hooked_components() {
+        let mut world = world_with_hooks();
+        world
+            .register_component_hooks::<Handle>()
+            .on_remove(|world, entity| {
+                log(world, "remove handle".to_string());
+                world.remove_component::<Marker>(entity);
+            });
+        world
+            .register_component_hooks::<Marker>()
+            .on_remove(|world, entity| {
+                assert!(world.has::<Marker>(entity));
+                log(world, "remove marker".to_string());
+            });
+        let entity = world.spawn((Marker, Handle(1)));
+        take_log(&mut world);
+        world.despawn(entity);
+        assert!(!world.is_alive(entity));
+        assert_eq!(take_log(&mut world), vec!["remove handle", "remove marker"]);
+    }
+
+    #[test]
+    fn hooks_may_move_or_despawn_the_first_of_a_batch() {
+        struct Extra(u32);
+        impl Component for Extra {}
+
+        let mut world = world_with_hooks();
+        world
+            .register_component_hooks::<Handle>()
+            .on_add(|world, entity| {
+                if world.get::<Handle>(entity).unwrap().0 == 0 {
+                    world.add_component_to_entity(entity, Extra(0));
+                }
+            });
+        let entities = world.spawn_batch((0..3).map(Handle));
+        assert!(world.has::<Extra>(entities[0]));
+        world.add_component_to_entity(entities[1], Extra(1));
+        assert_eq!(world.query::<&Extra>().iter().count(), 2);
+        assert_eq!(world.query::<&Handle>().iter().count(), 3);
+
+        world
+            .register_component_hooks::<Handle>()
+            .on_add(|world, entity| {
+                if world.get::<Handle>(entity).unwrap().0 == 0 {
+                    world.despawn(entity);
+                }
+            });
+        let entities = world.spawn_batch((0..3).map(Handle));
+        assert!(!world.is_alive(entities[0]));
+        assert_eq!(world.get::<Handle>(entities[2]).unwrap().0, 2);
+    }
+
+    #[test]
+    fn hooks_may_remove_other_components_of_the_bundle() {
+        let mut world = world_with_hooks();
+        world
+            .register_component_hooks::<Marker>()
+            .on_add(|world, entity| {
+                world.remove_component::<Handle>(entity);
+            });
+        let entity = world.spawn((Marker, Handle(1)));
+        assert!(!world.has::<Handle>(entity));
+        assert_eq!(take_log(&mut world), vec!["remove 1"]);
+    }
+}
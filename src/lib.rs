@@ -1,126 +1,704 @@
 #![allow(dead_code)]
 
-use std::cell::{Ref, RefCell, RefMut};
+use std::any::{type_name, TypeId};
+use std::collections::HashMap;
 
-pub trait ComponentVec {
-    fn push_none(&mut self);
+mod access;
+mod archetype;
+mod bundle;
+mod cell;
+mod change;
+mod command;
+mod component;
+mod entity;
+mod error;
+mod event;
+mod filter;
+mod hierarchy;
+mod hook;
+mod pool;
+mod query;
+#[cfg(feature = "serde")]
+mod registry;
+mod relation;
+mod removal;
+mod resource;
+mod schedule;
+mod storage;
+mod system;
+#[cfg(feature = "transform")]
+pub mod transform;
+#[cfg(feature = "serde")]
+mod value;
+
+pub use access::Access;
+use archetype::Archetypes;
+pub use archetype::{Archetype, EntityLocation};
+pub use bundle::{Bundle, BundleWriter, EntityBuilder};
+pub use cell::{Ref, RefMut};
+pub use change::Mut;
+pub use command::{CommandQueue, Commands};
+pub use component::{Component, StorageType};
+use entity::Entities;
+pub use entity::Entity;
+pub use error::BecsError;
+pub use event::{EventCursor, EventReader, EventWriter, Events};
+pub use filter::{Added, Changed, Or, QueryFilter, With, Without};
+pub use hierarchy::{Children, Parent};
+pub use hook::{ComponentHook, ComponentHooks};
+pub use query::{Query, QueryIter, WorldQuery};
+#[cfg(feature = "serde")]
+pub use registry::{RegistryError, SavedEntity, SavedWorld, TypeRegistry};
+pub use relation::{Relation, RelationSources, Relations};
+pub use removal::RemovedComponents;
+use resource::Resources;
+pub use resource::{Res, ResMut};
+pub use schedule::Schedule;
+use storage::{ComponentStorage, Components};
+pub use storage::{ComponentVecMut, ComponentVecRef};
+pub use system::{
+    FunctionSystem, IntoSystem, System, SystemParam, SystemParamFunction, SystemParamItem,
+};
+
+pub trait ComponentVec: Send + Sync {
+    fn storage_type(&self) -> StorageType;
+    fn move_row(&mut self, from: EntityLocation, to: usize);
+    fn contains(&self, entity: Entity) -> bool;
+    /// Removes the entity's component, if it has one, and records the removal at `tick`.
+    fn remove(&mut self, entity: Entity, location: EntityLocation, tick: u32);
+    /// Forgets the removals recorded at or before `tick`.
+    fn clear_removed(&mut self, tick: u32);
+    /// Reserves room for `additional` more components, in the archetype's column for table
+    /// storage.
+    fn reserve(&mut self, archetype: usize, additional: usize);
     fn as_any(&self) -> &dyn std::any::Any;
     fn as_any_mut(&mut self) -> &mut dyn std::any::Any;
 }
 
+/// Entities and their components.
+///
+/// The world is `Send + Sync`. Threads sharing it can borrow component vecs at the same time,
+/// and as with `RefCell`, a borrow that conflicts with one held elsewhere fails instead of
+/// blocking.
 pub struct World {
-    entities_count: usize,
-    component_vecs: Vec<Box<dyn ComponentVec>>,
+    entities: Entities,
+    archetypes: Archetypes,
+    component_vecs: HashMap<TypeId, Box<dyn ComponentVec>>,
+    // Component types not stored in archetype tables, which despawning has to visit separately
+    non_table_types: Vec<TypeId>,
+    resources: Resources,
+    hooks: HashMap<TypeId, ComponentHooks>,
+    builtin_hooks: HashMap<TypeId, ComponentHooks>,
+    // Update the `Events` resources added by `add_event`, keyed by event type so each is updated
+    // once however often it is added
+    event_updates: HashMap<TypeId, fn(&mut World)>,
+    change_tick: u32,
+    last_change_tick: u32,
 }
 
 impl World {
     pub fn new() -> Self {
-        World {
-            entities_count: 0,
-            component_vecs: Vec::new(),
+        let mut world = World {
+            entities: Entities::default(),
+            archetypes: Archetypes::new(),
+            component_vecs: HashMap::new(),
+            non_table_types: Vec::new(),
+            resources: Resources::default(),
+            hooks: HashMap::new(),
+            builtin_hooks: HashMap::new(),
+            event_updates: HashMap::new(),
+            change_tick: 1,
+            last_change_tick: 0,
+        };
+        hierarchy::register_hooks(&mut world);
+        world
+    }
+
+    pub fn new_entity(&mut self) -> Entity {
+        // New entities have no components, so they start in the empty archetype
+        self.spawn(())
+    }
+
+    /// Spawns an entity with every component of the bundle, e.g.
+    /// `world.spawn((Position(0.0), Velocity(1.0)))`.
+    pub fn spawn<B: Bundle>(&mut self, bundle: B) -> Entity {
+        let archetype = self
+            .archetypes
+            .with_bundle(0, TypeId::of::<B>(), B::table_types);
+        let (entity, location) = self.alloc_entity(archetype);
+        let hooked = self.bundle_hooked_types::<B>(entity);
+        bundle.write(&mut BundleWriter::new(self, entity, location));
+        self.run_insert_hooks(entity, &hooked);
+        entity
+    }
+
+    /// Spawns an entity for every bundle, returning them in order. Storage for as many entities
+    /// as the iterator's lower size bound is reserved up front, in every component vec the
+    /// bundle touches.
+    pub fn spawn_batch<B: Bundle>(&mut self, bundles: impl IntoIterator<Item = B>) -> Vec<Entity> {
+        let mut bundles = bundles.into_iter();
+        let mut entities = Vec::with_capacity(bundles.size_hint().0);
+
+        // The first entity creates whichever component vecs do not exist yet, so the rest can be
+        // reserved in all of them. Its hooks may have moved or despawned it, so the archetype
+        // comes from the bundle rather than from where the first entity ended up.
+        let first = match bundles.next() {
+            Some(bundle) => self.spawn(bundle),
+            None => return entities,
+        };
+        entities.push(first);
+
+        let archetype = self
+            .archetypes
+            .with_bundle(0, TypeId::of::<B>(), B::table_types);
+        let additional = bundles.size_hint().0;
+        self.entities.reserve_slots(additional);
+        self.archetypes.get_mut(archetype).reserve(additional);
+        let mut types = Vec::new();
+        B::component_types(&mut types);
+        for type_id in types {
+            self.component_vecs
+                .get_mut(&type_id)
+                .unwrap()
+                .reserve(archetype, additional);
         }
+
+        for bundle in bundles {
+            let (entity, location) = self.alloc_entity(archetype);
+            let hooked = self.bundle_hooked_types::<B>(entity);
+            bundle.write(&mut BundleWriter::new(self, entity, location));
+            self.run_insert_hooks(entity, &hooked);
+            entities.push(entity);
+        }
+        entities
+    }
+
+    /// Starts building an entity from components added one at a time.
+    pub fn build_entity(&mut self) -> EntityBuilder<'_> {
+        EntityBuilder::new(self)
+    }
+
+    pub fn despawn(&mut self, entity: Entity) -> bool {
+        self.try_despawn(entity).is_ok()
     }
 
-    pub fn new_entity(&mut self) -> usize {
-        // Create id;
-        let entity_id = self.entities_count;
+    pub fn try_despawn(&mut self, entity: Entity) -> Result<(), BecsError> {
+        // Stale handles must not remove the components of whatever entity reuses the slot
+        let location = self.entities.check(entity)?;
 
-        // Initialise components for entity to be none
-        for component_vec in self.component_vecs.iter_mut() {
-            component_vec.push_none();
+        // Hooks see the entity whole, and may despawn it themselves
+        let archetype = self.archetypes.get(location.archetype);
+        let types = archetype.types().iter().chain(&self.non_table_types);
+        let hooked = self.hooked_types(entity, types.copied());
+        self.run_remove_hooks(entity, &hooked);
+        if !self.is_alive(entity) {
+            return Ok(());
         }
+        let location = self.entities.location(entity).unwrap();
 
-        // Increment the number of entities
-        self.entities_count += 1;
+        let archetype = self.archetypes.get(location.archetype);
+        for type_id in archetype.types().iter().chain(&self.non_table_types) {
+            self.component_vecs.get_mut(type_id).unwrap().remove(
+                entity,
+                location,
+                self.change_tick,
+            );
+        }
+        self.remove_from_archetype(location);
 
-        // Return created entity id
-        entity_id
+        self.entities.free(entity);
+        Ok(())
     }
 
-    pub fn add_component_to_entity<ComponentType: 'static>(
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        self.entities.contains(entity)
+    }
+
+    pub fn add_component_to_entity<ComponentType: Component>(
         &mut self,
-        entity: usize,
+        entity: Entity,
         component: ComponentType,
     ) {
-        // Iterate through component vector to find the component vec that matches the component type
-        // and set the component for the entity as the supplied component
-        for component_vec in self.component_vecs.iter_mut() {
-            if let Some(component_vec) = component_vec
-                .as_any_mut()
-                .downcast_mut::<RefCell<Vec<Option<ComponentType>>>>()
-            {
-                component_vec.get_mut()[entity] = Some(component);
-                return;
-            }
+        if let Err(err) = self.try_add_component_to_entity(entity, component) {
+            panic!("{}", err);
         }
+    }
+
+    pub fn try_add_component_to_entity<ComponentType: Component>(
+        &mut self,
+        entity: Entity,
+        component: ComponentType,
+    ) -> Result<(), BecsError> {
+        // `on_remove` hooks see the value about to be replaced, and may change the entity
+        self.entities.check(entity)?;
+        let hooked = self.hooked_types(entity, Some(TypeId::of::<ComponentType>()));
+        self.run_remove_hooks(entity, &hooked);
+        let location = self.entities.check(entity)?;
+        let hooked = self.hooked_types(entity, Some(TypeId::of::<ComponentType>()));
+        let change_tick = self.change_tick;
+
+        // Find the component vec that matches the component type, creating it if it does not
+        // already exist
+        let component_vec = self.component_vec_or_insert::<ComponentType>();
 
-        // If the component vector does not already exists for the component type it needs to
-        // be created
-        let mut new_component_vec: Vec<Option<ComponentType>> =
-            Vec::with_capacity(self.entities_count);
+        // Replacing a component leaves the entity where it is
+        if component_vec.ticks(entity).is_some() {
+            component_vec.replace(entity, location, component, change_tick);
+            self.run_insert_hooks(entity, &hooked);
+            return Ok(());
+        }
 
-        // Set the component for all other entities to zero;
-        for _ in 0..self.entities_count {
-            new_component_vec.push(None);
+        // Only components stored in tables are part of the entity's archetype. Otherwise the
+        // entity moves to the archetype that also has the new component type.
+        let mut archetype = location.archetype;
+        if ComponentType::STORAGE == StorageType::Table {
+            archetype = self
+                .archetypes
+                .with_added(location.archetype, TypeId::of::<ComponentType>());
+            self.move_entity(entity, location, archetype);
         }
 
         // Set the component for the entity as the supplied component
-        new_component_vec[entity] = Some(component);
-        self.component_vecs
-            .push(Box::new(RefCell::new(new_component_vec)))
+        self.component_vec_mut::<ComponentType>().unwrap().push(
+            entity,
+            archetype,
+            component,
+            change_tick,
+        );
+        self.run_insert_hooks(entity, &hooked);
+        Ok(())
+    }
+
+    /// Adds every component of the bundle to the entity, replacing those it already has.
+    pub fn insert_bundle<B: Bundle>(&mut self, entity: Entity, bundle: B) {
+        if let Err(err) = self.try_insert_bundle(entity, bundle) {
+            panic!("{}", err);
+        }
     }
 
-    pub fn borrow_component_vec<ComponentType: 'static>(
+    pub fn try_insert_bundle<B: Bundle>(
+        &mut self,
+        entity: Entity,
+        bundle: B,
+    ) -> Result<(), BecsError> {
+        // `on_remove` hooks see the values about to be replaced, and may change the entity
+        self.entities.check(entity)?;
+        let hooked = self.bundle_hooked_types::<B>(entity);
+        self.run_remove_hooks(entity, &hooked);
+        let mut location = self.entities.check(entity)?;
+        let hooked = self.bundle_hooked_types::<B>(entity);
+
+        // The entity moves once, to the archetype that also has the bundle's table components.
+        // Components it already has are replaced where they are.
+        let target =
+            self.archetypes
+                .with_bundle(location.archetype, TypeId::of::<B>(), B::table_types);
+        if target != location.archetype {
+            location = self.move_entity(entity, location, target);
+        }
+        bundle.write(&mut BundleWriter::new(self, entity, location));
+        self.run_insert_hooks(entity, &hooked);
+        Ok(())
+    }
+
+    pub fn remove_component<ComponentType: Component>(
+        &mut self,
+        entity: Entity,
+    ) -> Option<ComponentType> {
+        self.try_remove_component(entity).ok()
+    }
+
+    pub fn try_remove_component<ComponentType: Component>(
+        &mut self,
+        entity: Entity,
+    ) -> Result<ComponentType, BecsError> {
+        // `on_remove` hooks still see the component, and may change the entity
+        self.entities.check(entity)?;
+        let hooked = self.hooked_types(entity, Some(TypeId::of::<ComponentType>()));
+        self.run_remove_hooks(entity, &hooked);
+        let location = self.entities.check(entity)?;
+        let tick = self.change_tick;
+
+        // Take the component out of the component vec, then move the rest of the entity's table
+        // components to the archetype without the component type
+        let component = self
+            .component_vec_mut::<ComponentType>()
+            .and_then(|component_vec| component_vec.take(entity, location, tick))
+            .ok_or(BecsError::ComponentMissing(type_name::<ComponentType>()))?;
+        if ComponentType::STORAGE == StorageType::Table {
+            let target = self
+                .archetypes
+                .with_removed(location.archetype, TypeId::of::<ComponentType>());
+            self.move_entity(entity, location, target);
+        }
+        Ok(component)
+    }
+
+    pub fn get<ComponentType: Component>(&self, entity: Entity) -> Option<Ref<'_, ComponentType>> {
+        // Only a missing component is expected here, borrow conflicts are bugs
+        match self.try_get(entity) {
+            Ok(component) => Some(component),
+            Err(err @ BecsError::AlreadyBorrowed(_)) => panic!("{}", err),
+            Err(_) => None,
+        }
+    }
+
+    pub fn try_get<ComponentType: Component>(
         &self,
-    ) -> Option<Ref<Vec<Option<ComponentType>>>> {
-        for component_vec in self.component_vecs.iter() {
-            if let Some(component_vec) = component_vec
-                .as_any()
-                .downcast_ref::<RefCell<Vec<Option<ComponentType>>>>()
-            {
-                return Some(component_vec.borrow());
-            }
+        entity: Entity,
+    ) -> Result<Ref<'_, ComponentType>, BecsError> {
+        let location = self.entities.check(entity)?;
+
+        let components = self.try_borrow_components::<ComponentType>()?;
+        Ref::filter_map(components, |components| components.get(entity, location))
+            .map_err(|_| BecsError::ComponentMissing(type_name::<ComponentType>()))
+    }
+
+    pub fn get_mut<ComponentType: Component>(
+        &self,
+        entity: Entity,
+    ) -> Option<RefMut<'_, ComponentType>> {
+        match self.try_get_mut(entity) {
+            Ok(component) => Some(component),
+            Err(err @ BecsError::AlreadyBorrowed(_)) => panic!("{}", err),
+            Err(_) => None,
         }
-        None
     }
 
-    pub fn borrow_component_vec_mut<ComponentType: 'static>(
+    pub fn try_get_mut<ComponentType: Component>(
         &self,
-    ) -> Option<RefMut<Vec<Option<ComponentType>>>> {
-        for component_vec in self.component_vecs.iter() {
-            if let Some(component_vec) = component_vec
-                .as_any()
-                .downcast_ref::<RefCell<Vec<Option<ComponentType>>>>()
-            {
-                return Some(component_vec.borrow_mut());
+        entity: Entity,
+    ) -> Result<RefMut<'_, ComponentType>, BecsError> {
+        let location = self.entities.check(entity)?;
+
+        let components = self.try_borrow_components_mut::<ComponentType>()?;
+        let component = RefMut::filter_map(components, |components| {
+            components.get_mut(entity, location)
+        })
+        .map_err(|_| BecsError::ComponentMissing(type_name::<ComponentType>()))?;
+
+        // Unlike `Mut`, the guard cannot tell reads from writes, so handing the component out
+        // mutably counts as a change
+        let component_vec = self.component_vec::<ComponentType>().unwrap();
+        component_vec
+            .ticks(entity)
+            .unwrap()
+            .set_changed(self.change_tick);
+        Ok(component)
+    }
+
+    pub fn has<ComponentType: Component>(&self, entity: Entity) -> bool {
+        self.entities.contains(entity)
+            && self
+                .component_vec::<ComponentType>()
+                .is_some_and(|component_vec| component_vec.ticks(entity).is_some())
+    }
+
+    pub fn borrow_component_vec<ComponentType: Component>(
+        &self,
+    ) -> Option<ComponentVecRef<'_, ComponentType>> {
+        let components = self.component_vec::<ComponentType>()?.components.borrow();
+        Some(ComponentVecRef::new(&self.entities, components))
+    }
+
+    pub fn try_borrow_component_vec<ComponentType: Component>(
+        &self,
+    ) -> Result<ComponentVecRef<'_, ComponentType>, BecsError> {
+        let components = self.try_borrow_components::<ComponentType>()?;
+        Ok(ComponentVecRef::new(&self.entities, components))
+    }
+
+    pub fn borrow_component_vec_mut<ComponentType: Component>(
+        &self,
+    ) -> Option<ComponentVecMut<'_, ComponentType>> {
+        let component_vec = self.component_vec::<ComponentType>()?;
+        Some(ComponentVecMut::new(
+            &self.entities,
+            component_vec.components.borrow_mut(),
+            &component_vec.ticks,
+            self.last_change_tick,
+            self.change_tick,
+        ))
+    }
+
+    pub fn try_borrow_component_vec_mut<ComponentType: Component>(
+        &self,
+    ) -> Result<ComponentVecMut<'_, ComponentType>, BecsError> {
+        let components = self.try_borrow_components_mut::<ComponentType>()?;
+        Ok(ComponentVecMut::new(
+            &self.entities,
+            components,
+            &self.component_vec::<ComponentType>().unwrap().ticks,
+            self.last_change_tick,
+            self.change_tick,
+        ))
+    }
+
+    /// Inserts a singleton that is not attached to any entity, returning the resource of the same
+    /// type it replaces.
+    pub fn insert_resource<R: Send + Sync + 'static>(&mut self, resource: R) -> Option<R> {
+        self.resources.insert(resource)
+    }
+
+    pub fn remove_resource<R: Send + Sync + 'static>(&mut self) -> Option<R> {
+        self.resources.remove()
+    }
+
+    pub fn contains_resource<R: Send + Sync + 'static>(&self) -> bool {
+        self.resources.contains::<R>()
+    }
+
+    pub fn resource<R: Send + Sync + 'static>(&self) -> Option<Ref<'_, R>> {
+        match self.try_resource() {
+            Ok(resource) => Some(resource),
+            Err(err @ BecsError::AlreadyBorrowed(_)) => panic!("{}", err),
+            Err(_) => None,
+        }
+    }
+
+    pub fn try_resource<R: Send + Sync + 'static>(&self) -> Result<Ref<'_, R>, BecsError> {
+        let resource = self
+            .resources
+            .get::<R>()
+            .ok_or(BecsError::ResourceMissing(type_name::<R>()))?
+            .try_borrow()
+            .ok_or(BecsError::AlreadyBorrowed(type_name::<R>()))?;
+        Ok(Ref::map(resource, |resource| {
+            resource.downcast_ref::<R>().unwrap()
+        }))
+    }
+
+    pub fn resource_mut<R: Send + Sync + 'static>(&self) -> Option<RefMut<'_, R>> {
+        match self.try_resource_mut() {
+            Ok(resource) => Some(resource),
+            Err(err @ BecsError::AlreadyBorrowed(_)) => panic!("{}", err),
+            Err(_) => None,
+        }
+    }
+
+    pub fn try_resource_mut<R: Send + Sync + 'static>(&self) -> Result<RefMut<'_, R>, BecsError> {
+        let resource = self
+            .resources
+            .get::<R>()
+            .ok_or(BecsError::ResourceMissing(type_name::<R>()))?
+            .try_borrow_mut()
+            .ok_or(BecsError::AlreadyBorrowed(type_name::<R>()))?;
+        Ok(RefMut::map(resource, |resource| {
+            resource.downcast_mut::<R>().unwrap()
+        }))
+    }
+
+    pub fn query<Q: WorldQuery>(&self) -> Query<'_, Q> {
+        self.query_filtered::<Q, ()>()
+    }
+
+    pub fn query_filtered<Q: WorldQuery, F: QueryFilter>(&self) -> Query<'_, Q, F> {
+        Query::new(self, self.last_change_tick, self.change_tick)
+    }
+
+    pub fn archetypes(&self) -> impl Iterator<Item = &Archetype> {
+        self.archetypes.iter()
+    }
+
+    pub fn change_tick(&self) -> u32 {
+        self.change_tick
+    }
+
+    /// Entities whose `ComponentType` was removed, or that were despawned with one, since the last
+    /// call to `clear_trackers`.
+    pub fn removed<ComponentType: Component>(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.removed_since::<ComponentType>(self.last_change_tick)
+    }
+
+    pub(crate) fn removed_since<ComponentType: Component>(
+        &self,
+        last_run: u32,
+    ) -> impl Iterator<Item = Entity> + '_ {
+        let removed = match self.component_vec::<ComponentType>() {
+            Some(component_vec) => &component_vec.removed[..],
+            None => &[],
+        };
+        removed
+            .iter()
+            .filter(move |&&(_, tick)| tick > last_run)
+            .map(|&(entity, _)| entity)
+    }
+
+    /// Marks the end of a frame: components added or changed before this call no longer match the
+    /// `Added` and `Changed` filters of queries made through `World::query`.
+    ///
+    /// Removals are kept for one more frame, so systems that run once per frame see each of them
+    /// through `RemovedComponents` whether they run before or after it. The same goes for the
+    /// events of every `Events` resource added with `add_event`.
+    pub fn clear_trackers(&mut self) {
+        for component_vec in self.component_vecs.values_mut() {
+            component_vec.clear_removed(self.last_change_tick);
+        }
+        let updates: Vec<fn(&mut World)> = self.event_updates.values().copied().collect();
+        for update in updates {
+            update(self);
+        }
+        self.last_change_tick = self.change_tick;
+        self.change_tick += 1;
+    }
+
+    // Advances the tick without ending the frame, so each system of a schedule writes at a tick
+    // of its own
+    pub(crate) fn increment_change_tick(&mut self) -> u32 {
+        self.change_tick += 1;
+        self.change_tick
+    }
+
+    // Allocates an entity at the end of the archetype, without any components yet
+    pub(crate) fn alloc_entity(&mut self, archetype: usize) -> (Entity, EntityLocation) {
+        self.flush_entities();
+
+        // Create id, reusing the slot of a despawned entity if possible
+        let entity = self.entities.alloc(EntityLocation::EMPTY);
+        let row = self.archetypes.get_mut(archetype).push(entity);
+        let location = EntityLocation { archetype, row };
+        self.entities.set_location(entity, location);
+        (entity, location)
+    }
+
+    // Allocates the entities reserved by `Commands::spawn`, which start without components in the
+    // empty archetype. Reservations are not tied to their queue, so this happens whether or not
+    // the queue is ever applied.
+    pub(crate) fn flush_entities(&mut self) {
+        let archetype = self.archetypes.get_mut(0);
+        self.entities.flush(|entity| EntityLocation {
+            archetype: 0,
+            row: archetype.push(entity),
+        });
+    }
+
+    // Moves the entity's components to the end of the target archetype's columns. Component
+    // types the target archetype does not have must already have been taken out.
+    fn move_entity(
+        &mut self,
+        entity: Entity,
+        location: EntityLocation,
+        target: usize,
+    ) -> EntityLocation {
+        let target_archetype = self.archetypes.get(target);
+        for type_id in self.archetypes.get(location.archetype).types() {
+            if target_archetype.contains(*type_id) {
+                self.component_vecs
+                    .get_mut(type_id)
+                    .unwrap()
+                    .move_row(location, target);
             }
         }
-        None
+        self.remove_from_archetype(location);
+
+        let row = self.archetypes.get_mut(target).push(entity);
+        let new_location = EntityLocation {
+            archetype: target,
+            row,
+        };
+        self.entities.set_location(entity, new_location);
+        new_location
+    }
+
+    // Removes the row from the archetype's entities, updating the location of the entity that
+    // takes its place. Columns are updated the same way by `ComponentVec::move_row` and
+    // `ComponentVec::remove_row`.
+    fn remove_from_archetype(&mut self, location: EntityLocation) {
+        if let Some(moved) = self
+            .archetypes
+            .get_mut(location.archetype)
+            .swap_remove(location.row)
+        {
+            self.entities.set_location(moved, location);
+        }
     }
-}
 
-impl<T: 'static> ComponentVec for RefCell<Vec<Option<T>>> {
-    fn push_none(&mut self) {
-        self.get_mut().push(None);
+    fn has_type(&self, entity: Entity, type_id: TypeId) -> bool {
+        self.component_vecs
+            .get(&type_id)
+            .is_some_and(|component_vec| component_vec.contains(entity))
     }
 
-    fn as_any(&self) -> &dyn std::any::Any {
-        self as &dyn std::any::Any
+    fn bundle_hooked_types<B: Bundle>(&self, entity: Entity) -> Vec<(TypeId, bool)> {
+        let mut types = Vec::new();
+        B::component_types(&mut types);
+        self.hooked_types(entity, types)
     }
 
-    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
-        self as &mut dyn std::any::Any
+    fn component_vec_or_insert<ComponentType: Component>(
+        &mut self,
+    ) -> &mut ComponentStorage<ComponentType> {
+        let type_id = TypeId::of::<ComponentType>();
+        if !self.component_vecs.contains_key(&type_id) {
+            if ComponentType::STORAGE != StorageType::Table {
+                self.non_table_types.push(type_id);
+            }
+            self.component_vecs.insert(
+                type_id,
+                Box::new(ComponentStorage::<ComponentType>::new(
+                    ComponentType::STORAGE,
+                )),
+            );
+        }
+        self.component_vec_mut::<ComponentType>().unwrap()
+    }
+
+    fn component_vec<ComponentType: Component>(&self) -> Option<&ComponentStorage<ComponentType>> {
+        self.component_vecs
+            .get(&TypeId::of::<ComponentType>())?
+            .as_any()
+            .downcast_ref::<ComponentStorage<ComponentType>>()
+    }
+
+    fn component_vec_mut<ComponentType: Component>(
+        &mut self,
+    ) -> Option<&mut ComponentStorage<ComponentType>> {
+        self.component_vecs
+            .get_mut(&TypeId::of::<ComponentType>())?
+            .as_any_mut()
+            .downcast_mut::<ComponentStorage<ComponentType>>()
+    }
+
+    fn try_borrow_components<ComponentType: Component>(
+        &self,
+    ) -> Result<Ref<'_, Components<ComponentType>>, BecsError> {
+        self.component_vec::<ComponentType>()
+            .ok_or(BecsError::ComponentMissing(type_name::<ComponentType>()))?
+            .components
+            .try_borrow()
+            .ok_or(BecsError::AlreadyBorrowed(type_name::<ComponentType>()))
+    }
+
+    fn try_borrow_components_mut<ComponentType: Component>(
+        &self,
+    ) -> Result<RefMut<'_, Components<ComponentType>>, BecsError> {
+        self.component_vec::<ComponentType>()
+            .ok_or(BecsError::ComponentMissing(type_name::<ComponentType>()))?
+            .components
+            .try_borrow_mut()
+            .ok_or(BecsError::AlreadyBorrowed(type_name::<ComponentType>()))
+    }
+}
+
+impl Default for World {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::World;
+    use std::sync::mpsc;
+    use std::thread;
+
+    use crate::{BecsError, Component, ComponentVecMut, Query, World};
 
     #[test]
     fn systems() {
         struct Health(i32);
+        impl Component for Health {}
         struct Name(&'static str);
+        impl Component for Name {}
 
         let mut world = World::new();
         let entity = world.new_entity();
@@ -131,12 +709,180 @@ mod tests {
         let mut names = world.borrow_component_vec_mut::<Name>().unwrap();
         let zip = healths.iter_mut().zip(names.iter_mut());
 
-        for (health, name) in
-            zip.filter_map(|(health, name)| Some((health.as_mut()?, name.as_mut()?)))
-        {
+        for (mut health, name) in zip.filter_map(|(health, name)| Some((health?, name?))) {
             health.0 = 100;
 
             println!("{} has been healed to {}", name.0, health.0);
         }
     }
+
+    #[test]
+    fn despawn_recycles_index() {
+        struct Health(i32);
+        impl Component for Health {}
+
+        let mut world = World::new();
+        let first = world.new_entity();
+        world.add_component_to_entity(first, Health(10));
+
+        assert!(world.despawn(first));
+        assert!(!world.is_alive(first));
+        assert!(!world.despawn(first));
+
+        let second = world.new_entity();
+        assert_eq!(second.index(), first.index());
+        assert_ne!(second, first);
+        assert!(world.get::<Health>(second).is_none());
+    }
+
+    #[test]
+    fn remove_component() {
+        struct Stunned;
+        impl Component for Stunned {}
+        struct Health(i32);
+        impl Component for Health {}
+
+        let mut world = World::new();
+        let entity = world.new_entity();
+        world.add_component_to_entity(entity, Stunned);
+        world.add_component_to_entity(entity, Health(10));
+
+        assert!(world.remove_component::<Stunned>(entity).is_some());
+        assert!(world.remove_component::<Stunned>(entity).is_none());
+        assert_eq!(world.remove_component::<Health>(entity).unwrap().0, 10);
+
+        world.despawn(entity);
+        assert!(world.remove_component::<Health>(entity).is_none());
+    }
+
+    #[test]
+    fn get_single_component() {
+        struct Health(i32);
+        impl Component for Health {}
+        struct Name(&'static str);
+        impl Component for Name {}
+
+        let mut world = World::new();
+        let entity = world.new_entity();
+        world.add_component_to_entity(entity, Health(10));
+        let nameless = world.new_entity();
+
+        assert!(world.has::<Health>(entity));
+        assert!(!world.has::<Name>(entity));
+        assert!(!world.has::<Health>(nameless));
+
+        world.get_mut::<Health>(entity).unwrap().0 += 5;
+        assert_eq!(world.get::<Health>(entity).unwrap().0, 15);
+
+        world.despawn(entity);
+        assert!(world.get::<Health>(entity).is_none());
+        assert!(world.get_mut::<Health>(entity).is_none());
+        assert!(!world.has::<Health>(entity));
+    }
+
+    #[test]
+    fn fallible_api_reports_errors() {
+        struct Health(i32);
+        impl Component for Health {}
+
+        let mut world = World::new();
+        let entity = world.new_entity();
+        let nameless = world.new_entity();
+        world.add_component_to_entity(entity, Health(10));
+
+        assert_eq!(
+            world.try_get::<Health>(nameless).err(),
+            Some(BecsError::ComponentMissing(std::any::type_name::<Health>()))
+        );
+        {
+            let _healths = world.borrow_component_vec_mut::<Health>().unwrap();
+            assert!(matches!(
+                world.try_get::<Health>(entity),
+                Err(BecsError::AlreadyBorrowed(_))
+            ));
+            assert!(matches!(
+                world.try_borrow_component_vec::<Health>(),
+                Err(BecsError::AlreadyBorrowed(_))
+            ));
+        }
+
+        world.try_despawn(entity).unwrap();
+        assert_eq!(
+            world.try_despawn(entity),
+            Err(BecsError::StaleEntity(entity))
+        );
+        assert_eq!(
+            world.try_add_component_to_entity(entity, Health(1)),
+            Err(BecsError::StaleEntity(entity))
+        );
+
+        let mut other_world = World::new();
+        let unknown = other_world.new_entity();
+        assert_eq!(
+            World::new().try_remove_component::<Health>(unknown).err(),
+            Some(BecsError::NoSuchEntity(unknown))
+        );
+    }
+
+    #[test]
+    fn world_is_shared_between_threads() {
+        struct Health(i32);
+        impl Component for Health {}
+        struct Name(&'static str);
+        impl Component for Name {}
+
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<World>();
+        assert_send_sync::<ComponentVecMut<'static, Health>>();
+        assert_send_sync::<Query<'static, (&'static mut Health, &'static Name)>>();
+
+        let mut world = World::new();
+        let entity = world.new_entity();
+        world.add_component_to_entity(entity, Health(10));
+        world.add_component_to_entity(entity, Name("Somebody"));
+
+        let (borrowed_sender, borrowed) = mpsc::channel();
+        let (release_sender, release) = mpsc::channel::<()>();
+        thread::scope(|scope| {
+            let world = &world;
+            scope.spawn(move || {
+                let mut healths = world.borrow_component_vec_mut::<Health>().unwrap();
+                healths.get_mut(entity).unwrap().0 = 20;
+                borrowed_sender.send(()).unwrap();
+                release.recv().unwrap();
+            });
+
+            // Only the component vec borrowed by the other thread is unavailable
+            borrowed.recv().unwrap();
+            assert!(matches!(
+                world.try_get::<Health>(entity),
+                Err(BecsError::AlreadyBorrowed(_))
+            ));
+            assert_eq!(world.get::<Name>(entity).unwrap().0, "Somebody");
+            release_sender.send(()).unwrap();
+        });
+        assert_eq!(world.get::<Health>(entity).unwrap().0, 20);
+
+        let world = thread::spawn(move || {
+            world.despawn(entity);
+            world
+        })
+        .join()
+        .unwrap();
+        assert!(!world.is_alive(entity));
+    }
+
+    #[test]
+    #[should_panic]
+    fn stale_entity_cannot_add_component() {
+        struct Health(i32);
+        impl Component for Health {}
+
+        let mut world = World::new();
+        let first = world.new_entity();
+        world.despawn(first);
+        world.new_entity();
+
+        world.add_component_to_entity(first, Health(10));
+    }
 }
@@ -0,0 +1,150 @@
+use std::mem;
+use std::num::NonZeroUsize;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// Worker threads that live as long as the pool, one per available core.
+pub(crate) struct ThreadPool {
+    jobs: Mutex<Option<Sender<Job>>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl ThreadPool {
+    pub fn new() -> Self {
+        let threads = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let workers = (0..threads)
+            .map(|index| {
+                let receiver = receiver.clone();
+                thread::Builder::new()
+                    .name(format!("becs-worker-{}", index))
+                    .spawn(move || work(&receiver))
+                    .expect("failed to spawn a worker thread")
+            })
+            .collect();
+        ThreadPool {
+            jobs: Mutex::new(Some(sender)),
+            workers,
+        }
+    }
+
+    pub fn threads(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Runs every task on the workers and returns once all of them have finished. A panic in a
+    /// task is resumed on the calling thread after the others finish.
+    pub fn scope<'a>(&self, tasks: Vec<Box<dyn FnOnce() + Send + 'a>>) {
+        let (done, finished) = mpsc::channel();
+        let count = tasks.len();
+        let mut sent = 0;
+        {
+            let jobs = self.jobs.lock().unwrap();
+            let jobs = jobs.as_ref().unwrap();
+            for task in tasks {
+                let done = done.clone();
+                let job: Box<dyn FnOnce() + Send + 'a> = Box::new(move || {
+                    let result = panic::catch_unwind(AssertUnwindSafe(task));
+                    let _ = done.send(result);
+                });
+                // SAFETY: the job borrows from the caller for 'a, so it must be gone before this
+                // function returns or unwinds. Every job owns a clone of `done`, which it drops
+                // only once it has run or been dropped unrun, and the loop below waits until no
+                // clone is left. Nothing from the first send to the end of that loop can panic:
+                // a failed send hands the job back to be dropped on the spot, task panics are
+                // caught on the workers, and the lock is already held.
+                let job: Job = unsafe { mem::transmute(job) };
+                if jobs.send(job).is_err() {
+                    break;
+                }
+                sent += 1;
+            }
+        }
+        drop(done);
+
+        let mut panicked = None;
+        let mut finished_count = 0;
+        for result in finished.iter() {
+            finished_count += 1;
+            if let Err(payload) = result {
+                panicked.get_or_insert(payload);
+            }
+        }
+        // Only now that no job is left can this panic without leaving borrows of 'a behind
+        assert_eq!(sent, count, "worker threads have stopped");
+        assert_eq!(finished_count, sent, "a worker dropped its task");
+        if let Some(payload) = panicked {
+            panic::resume_unwind(payload);
+        }
+    }
+}
+
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        // Closing the channel stops the workers once they run out of jobs
+        self.jobs.lock().unwrap().take();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn work(jobs: &Mutex<Receiver<Job>>) {
+    loop {
+        let job = jobs.lock().unwrap().recv();
+        match job {
+            Ok(job) => job(),
+            Err(_) => break,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::ThreadPool;
+
+    #[test]
+    fn scope_runs_tasks_borrowing_the_caller() {
+        let pool = ThreadPool::new();
+        let counter = AtomicUsize::new(0);
+        for _ in 0..3 {
+            let tasks: Vec<Box<dyn FnOnce() + Send + '_>> = (0..16)
+                .map(|_| {
+                    Box::new(|| {
+                        counter.fetch_add(1, Ordering::Relaxed);
+                    }) as Box<dyn FnOnce() + Send>
+                })
+                .collect();
+            pool.scope(tasks);
+        }
+        assert_eq!(counter.load(Ordering::Relaxed), 48);
+    }
+
+    #[test]
+    fn panics_reach_the_caller_after_every_task_finishes() {
+        let pool = ThreadPool::new();
+        let counter = AtomicUsize::new(0);
+        let tasks: Vec<Box<dyn FnOnce() + Send + '_>> = vec![
+            Box::new(|| panic!("task failed")),
+            Box::new(|| {
+                counter.fetch_add(1, Ordering::Relaxed);
+            }),
+        ];
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| pool.scope(tasks)));
+        assert!(result.is_err());
+        assert_eq!(counter.load(Ordering::Relaxed), 1);
+
+        // The workers survive the panic
+        pool.scope(vec![Box::new(|| {
+            counter.fetch_add(1, Ordering::Relaxed);
+        })]);
+        assert_eq!(counter.load(Ordering::Relaxed), 2);
+    }
+}
@@ -0,0 +1,419 @@
+use std::any::TypeId;
+use std::marker::PhantomData;
+
+use crate::access::Access;
+use crate::archetype::{Archetype, Archetypes, EntityLocation};
+use crate::cell::{Ref, RefMut};
+use crate::entity::Entities;
+use crate::filter::QueryFilter;
+use crate::storage::{ComponentTicks, Components, ComponentsPtr};
+use crate::{Component, Entity, Mut, StorageType, World};
+
+/// A set of components that can be fetched together for one entity, e.g. `(&Health, &mut Name)`.
+///
+/// Implemented for `Entity`, `&T`, `&mut T`, `Option` of a query and tuples of up to eight
+/// queries.
+pub trait WorldQuery {
+    type Item<'q>;
+    type Fetch<'w>;
+
+    /// Borrows the component vecs this query reads from, returning `None` if a required
+    /// component vec does not exist so nothing can match. Components written through the fetch
+    /// are marked as changed at `this_run`.
+    fn init_fetch(world: &World, last_run: u32, this_run: u32) -> Option<Self::Fetch<'_>>;
+
+    /// Whether entities of the archetype can match the query, so whole archetypes can be skipped.
+    fn matches_archetype(archetype: &Archetype) -> bool;
+
+    /// Adds the component types the query reads and writes.
+    fn update_access(access: &mut Access);
+
+    /// Fetches the item for the entity at `location`, or `None` if it does not match the query.
+    ///
+    /// # Safety
+    ///
+    /// Items fetched for the same entity must not be alive at the same time, as they may hand
+    /// out mutable references to the same component.
+    unsafe fn fetch<'q>(
+        fetch: &'q Self::Fetch<'_>,
+        entity: Entity,
+        location: EntityLocation,
+    ) -> Option<Self::Item<'q>>;
+}
+
+impl WorldQuery for Entity {
+    type Item<'q> = Entity;
+    type Fetch<'w> = ();
+
+    fn init_fetch(_world: &World, _last_run: u32, _this_run: u32) -> Option<Self::Fetch<'_>> {
+        Some(())
+    }
+
+    fn matches_archetype(_archetype: &Archetype) -> bool {
+        true
+    }
+
+    fn update_access(_access: &mut Access) {}
+
+    unsafe fn fetch<'q>(
+        _fetch: &'q Self::Fetch<'_>,
+        entity: Entity,
+        _location: EntityLocation,
+    ) -> Option<Self::Item<'q>> {
+        Some(entity)
+    }
+}
+
+/// Shared borrow of a component vec.
+pub struct FetchRef<'w, T>(Ref<'w, Components<T>>);
+
+impl<T: Component> WorldQuery for &T {
+    type Item<'q> = &'q T;
+    type Fetch<'w> = FetchRef<'w, T>;
+
+    fn init_fetch(world: &World, _last_run: u32, _this_run: u32) -> Option<Self::Fetch<'_>> {
+        Some(FetchRef(world.component_vec::<T>()?.components.borrow()))
+    }
+
+    fn matches_archetype(archetype: &Archetype) -> bool {
+        T::STORAGE != StorageType::Table || archetype.contains(TypeId::of::<T>())
+    }
+
+    fn update_access(access: &mut Access) {
+        access.add_read::<T>();
+    }
+
+    unsafe fn fetch<'q>(
+        fetch: &'q Self::Fetch<'_>,
+        entity: Entity,
+        location: EntityLocation,
+    ) -> Option<Self::Item<'q>> {
+        fetch.0.get(entity, location)
+    }
+}
+
+/// Mutable borrow of a component vec that can hand out mutable references to several distinct
+/// components at once.
+pub struct FetchMut<'w, T> {
+    // Only kept to hold the borrow for as long as the fetch is alive
+    _guard: RefMut<'w, Components<T>>,
+    components: ComponentsPtr<T>,
+    ticks: &'w [Option<ComponentTicks>],
+    last_run: u32,
+    this_run: u32,
+}
+
+/// Fetches `Mut<T>`, which marks the component as changed when it is written through.
+impl<T: Component> WorldQuery for &mut T {
+    type Item<'q> = Mut<'q, T>;
+    type Fetch<'w> = FetchMut<'w, T>;
+
+    fn init_fetch(world: &World, last_run: u32, this_run: u32) -> Option<Self::Fetch<'_>> {
+        let component_vec = world.component_vec::<T>()?;
+        let mut guard = component_vec.components.borrow_mut();
+        let components = guard.ptr();
+        Some(FetchMut {
+            _guard: guard,
+            components,
+            ticks: &component_vec.ticks,
+            last_run,
+            this_run,
+        })
+    }
+
+    fn matches_archetype(archetype: &Archetype) -> bool {
+        T::STORAGE != StorageType::Table || archetype.contains(TypeId::of::<T>())
+    }
+
+    fn update_access(access: &mut Access) {
+        access.add_write::<T>();
+    }
+
+    unsafe fn fetch<'q>(
+        fetch: &'q Self::Fetch<'_>,
+        entity: Entity,
+        location: EntityLocation,
+    ) -> Option<Self::Item<'q>> {
+        // The caller guarantees no other item for this entity is alive, so this is the only
+        // reference to the component
+        let component = fetch.components.get(entity, location)?;
+        let ticks = fetch.ticks[entity.index() as usize].as_ref()?;
+        Some(Mut::new(component, ticks, fetch.last_run, fetch.this_run))
+    }
+}
+
+/// Matches every entity, fetching `Some` for those that match the inner query, e.g.
+/// `(&Position, Option<&Velocity>)`.
+impl<Q: WorldQuery> WorldQuery for Option<Q> {
+    type Item<'q> = Option<Q::Item<'q>>;
+    type Fetch<'w> = Option<Q::Fetch<'w>>;
+
+    fn init_fetch(world: &World, last_run: u32, this_run: u32) -> Option<Self::Fetch<'_>> {
+        Some(Q::init_fetch(world, last_run, this_run))
+    }
+
+    fn matches_archetype(_archetype: &Archetype) -> bool {
+        true
+    }
+
+    fn update_access(access: &mut Access) {
+        Q::update_access(access);
+    }
+
+    unsafe fn fetch<'q>(
+        fetch: &'q Self::Fetch<'_>,
+        entity: Entity,
+        location: EntityLocation,
+    ) -> Option<Self::Item<'q>> {
+        Some(
+            fetch
+                .as_ref()
+                .and_then(|fetch| Q::fetch(fetch, entity, location)),
+        )
+    }
+}
+
+macro_rules! impl_world_query_tuple {
+    ($($name:ident),*) => {
+        #[allow(non_snake_case)]
+        impl<$($name: WorldQuery),*> WorldQuery for ($($name,)*) {
+            type Item<'q> = ($($name::Item<'q>,)*);
+            type Fetch<'w> = ($($name::Fetch<'w>,)*);
+
+            fn init_fetch(
+                world: &World,
+                last_run: u32,
+                this_run: u32,
+            ) -> Option<Self::Fetch<'_>> {
+                Some(($($name::init_fetch(world, last_run, this_run)?,)*))
+            }
+
+            fn matches_archetype(archetype: &Archetype) -> bool {
+                $($name::matches_archetype(archetype))&&*
+            }
+
+            fn update_access(access: &mut Access) {
+                $($name::update_access(access);)*
+            }
+
+            unsafe fn fetch<'q>(
+                fetch: &'q Self::Fetch<'_>,
+                entity: Entity,
+                location: EntityLocation,
+            ) -> Option<Self::Item<'q>> {
+                let ($($name,)*) = fetch;
+                Some(($($name::fetch($name, entity, location)?,)*))
+            }
+        }
+    };
+}
+
+impl_world_query_tuple!(A);
+impl_world_query_tuple!(A, B);
+impl_world_query_tuple!(A, B, C);
+impl_world_query_tuple!(A, B, C, D);
+impl_world_query_tuple!(A, B, C, D, E);
+impl_world_query_tuple!(A, B, C, D, E, F);
+impl_world_query_tuple!(A, B, C, D, E, F, G);
+impl_world_query_tuple!(A, B, C, D, E, F, G, H);
+
+/// Borrowed view of every entity matching `Q` and the filter `F`, created by `World::query` and
+/// `World::query_filtered`.
+///
+/// The component vecs stay borrowed for as long as the query is alive.
+pub struct Query<'w, Q: WorldQuery, F: QueryFilter = ()> {
+    entities: &'w Entities,
+    archetypes: &'w Archetypes,
+    fetch: Option<(Q::Fetch<'w>, F::Fetch<'w>)>,
+}
+
+impl<'w, Q: WorldQuery, F: QueryFilter> Query<'w, Q, F> {
+    pub(crate) fn new(world: &'w World, last_run: u32, this_run: u32) -> Self {
+        let fetch = Q::init_fetch(world, last_run, this_run)
+            .map(|fetch| (fetch, F::init_fetch(world, last_run)));
+        Query {
+            entities: &world.entities,
+            archetypes: &world.archetypes,
+            fetch,
+        }
+    }
+
+    pub fn iter(&mut self) -> QueryIter<'_, 'w, Q, F> {
+        QueryIter {
+            archetypes: self.archetypes.iter().enumerate(),
+            archetype: None,
+            fetch: self.fetch.as_ref(),
+            row: 0,
+            marker: PhantomData,
+        }
+    }
+
+    /// Fetches the query item for a single entity, or `None` if it is dead or does not match.
+    pub fn get(&mut self, entity: Entity) -> Option<Q::Item<'_>> {
+        let location = self.entities.location(entity)?;
+        let archetype = self.archetypes.get(location.archetype);
+        if !Q::matches_archetype(archetype) || !F::matches_archetype(archetype) {
+            return None;
+        }
+
+        let (fetch, filter) = self.fetch.as_ref()?;
+        if !F::matches(filter, archetype, entity, location) {
+            return None;
+        }
+
+        // Safe as the returned item keeps the query mutably borrowed
+        unsafe { Q::fetch(fetch, entity, location) }
+    }
+}
+
+impl<'q, 'w, Q: WorldQuery, F: QueryFilter> IntoIterator for &'q mut Query<'w, Q, F> {
+    type Item = Q::Item<'q>;
+    type IntoIter = QueryIter<'q, 'w, Q, F>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+pub struct QueryIter<'q, 'w, Q: WorldQuery, F: QueryFilter = ()> {
+    archetypes: std::iter::Enumerate<std::slice::Iter<'w, Archetype>>,
+    // Archetype whose rows are being iterated, along with its id
+    archetype: Option<(usize, &'w Archetype)>,
+    fetch: Option<&'q (Q::Fetch<'w>, F::Fetch<'w>)>,
+    row: usize,
+    marker: PhantomData<&'q mut Query<'w, Q, F>>,
+}
+
+impl<'q, 'w, Q: WorldQuery, F: QueryFilter> Iterator for QueryIter<'q, 'w, Q, F> {
+    type Item = Q::Item<'q>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (fetch, filter) = self.fetch?;
+        loop {
+            let (id, archetype) = match self.archetype {
+                Some((id, archetype)) if self.row < archetype.len() => (id, archetype),
+                _ => {
+                    // Move on to the next archetype that can match
+                    let (id, archetype) = self.archetypes.next()?;
+                    if Q::matches_archetype(archetype) && F::matches_archetype(archetype) {
+                        self.archetype = Some((id, archetype));
+                        self.row = 0;
+                    }
+                    continue;
+                }
+            };
+
+            let row = self.row;
+            self.row += 1;
+            let entity = archetype.entities()[row];
+            let location = EntityLocation { archetype: id, row };
+            if !F::matches(filter, archetype, entity, location) {
+                continue;
+            }
+
+            // Safe as every entity is visited once and the iterator keeps the query mutably
+            // borrowed
+            if let Some(item) = unsafe { Q::fetch(fetch, entity, location) } {
+                return Some(item);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Component, Entity, World};
+
+    struct Health(i32);
+    impl Component for Health {}
+    struct Name(&'static str);
+    impl Component for Name {}
+
+    #[test]
+    fn query_matches_entities_with_all_components() {
+        let mut world = World::new();
+        let somebody = world.new_entity();
+        world.add_component_to_entity(somebody, Name("Somebody"));
+        world.add_component_to_entity(somebody, Health(10));
+        let nameless = world.new_entity();
+        world.add_component_to_entity(nameless, Health(5));
+
+        for (mut health, name) in world.query::<(&mut Health, &Name)>().iter() {
+            health.0 = 100;
+            assert_eq!(name.0, "Somebody");
+        }
+
+        let healths: Vec<(Entity, i32)> = world
+            .query::<(Entity, &Health)>()
+            .iter()
+            .map(|(entity, health)| (entity, health.0))
+            .collect();
+        assert_eq!(healths, vec![(somebody, 100), (nameless, 5)]);
+    }
+
+    #[test]
+    fn query_skips_despawned_entities() {
+        let mut world = World::new();
+        let first = world.new_entity();
+        world.add_component_to_entity(first, Health(1));
+        let second = world.new_entity();
+        world.add_component_to_entity(second, Health(2));
+        world.despawn(first);
+
+        let mut query = world.query::<(Entity, &Health)>();
+        assert_eq!(query.iter().count(), 1);
+        assert!(query.get(first).is_none());
+        assert_eq!(query.get(second).unwrap().1 .0, 2);
+    }
+
+    #[test]
+    fn query_on_missing_component_is_empty() {
+        let mut world = World::new();
+        let entity = world.new_entity();
+        world.add_component_to_entity(entity, Health(1));
+
+        assert_eq!(world.query::<(&Health, &Name)>().iter().count(), 0);
+    }
+
+    #[test]
+    fn query_with_optional_components() {
+        let mut world = World::new();
+        let somebody = world.new_entity();
+        world.add_component_to_entity(somebody, Name("Somebody"));
+        world.add_component_to_entity(somebody, Health(10));
+        let nameless = world.new_entity();
+        world.add_component_to_entity(nameless, Health(5));
+
+        for (health, name) in world.query::<(&Health, Option<&mut Name>)>().iter() {
+            if let Some(mut name) = name {
+                name.0 = "Healthy";
+            }
+            assert!(health.0 > 0);
+        }
+
+        let names: Vec<Option<&str>> = world
+            .query::<(&Health, Option<&Name>)>()
+            .iter()
+            .map(|(_, name)| name.map(|name| name.0))
+            .collect();
+        assert_eq!(names, vec![Some("Healthy"), None]);
+
+        // A missing component vec only means the optional component is never present
+        struct Velocity;
+        impl Component for Velocity {}
+        assert_eq!(
+            world.query::<(&Health, Option<&Velocity>)>().iter().count(),
+            2
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn query_with_aliasing_mutable_access_panics() {
+        let mut world = World::new();
+        let entity = world.new_entity();
+        world.add_component_to_entity(entity, Health(1));
+
+        world.query::<(&mut Health, &Health)>();
+    }
+}
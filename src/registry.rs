@@ -0,0 +1,461 @@
+use std::error::Error;
+use std::fmt;
+use std::mem;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::archetype::EntityLocation;
+use crate::hierarchy;
+use crate::value::{self, Value, ValueError};
+use crate::{Component, Entity, Relation, Relations, World};
+
+struct ComponentRegistration {
+    name: &'static str,
+    save: fn(&World, Entity) -> Option<Result<Value, ValueError>>,
+    load: fn(&mut World, Entity, &Value) -> Result<(), ValueError>,
+}
+
+struct ResourceRegistration {
+    name: &'static str,
+    save: fn(&World) -> Option<Result<Value, ValueError>>,
+    load: fn(&mut World, &Value) -> Result<(), ValueError>,
+}
+
+/// Component and resource types that worlds are saved with, each under a stable name.
+///
+/// Storage is type-erased, so only the registered types can be found in a world and recreated
+/// in another. `save` turns their values into a `SavedWorld`, which any serde format can write
+/// and read back, and `load` turns it into a world again. Every entity gets the handle it had
+/// when it was saved, which keeps the entities that components refer to valid.
+///
+/// ```
+/// use becs::{Component, Entity, SavedWorld, TypeRegistry, World};
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Target(Entity);
+/// impl Component for Target {}
+///
+/// let mut registry = TypeRegistry::new();
+/// registry.register_component::<Target>("game::Target");
+///
+/// let mut world = World::new();
+/// let enemy = world.new_entity();
+/// let turret = world.spawn(Target(enemy));
+///
+/// let ron = ron::to_string(&registry.save(&world).unwrap()).unwrap();
+/// let saved: SavedWorld = ron::from_str(&ron).unwrap();
+/// let loaded = registry.load(&saved).unwrap();
+/// assert_eq!(loaded.get::<Target>(turret).unwrap().0, enemy);
+/// assert!(loaded.is_alive(enemy));
+/// ```
+#[derive(Default)]
+pub struct TypeRegistry {
+    components: Vec<ComponentRegistration>,
+    resources: Vec<ResourceRegistration>,
+}
+
+impl TypeRegistry {
+    pub fn new() -> Self {
+        TypeRegistry::default()
+    }
+
+    /// Registers the component type under `name`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a component type is already registered under `name`.
+    pub fn register_component<T: Component + Serialize + DeserializeOwned>(
+        &mut self,
+        name: &'static str,
+    ) -> &mut Self {
+        self.push_component(ComponentRegistration {
+            name,
+            save: |world, entity| Some(value::to_value(&*world.get::<T>(entity)?)),
+            load: |world, entity, value| {
+                world.add_component_to_entity(entity, value::from_value::<T>(value)?);
+                Ok(())
+            },
+        })
+    }
+
+    /// Registers the relations of kind `R` under `name`. They are loaded through
+    /// `World::add_relation`, so the targets' `RelationSources` are rebuilt along with them.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a component type is already registered under `name`.
+    pub fn register_relation<R: Relation + Serialize + DeserializeOwned>(
+        &mut self,
+        name: &'static str,
+    ) -> &mut Self {
+        self.push_component(ComponentRegistration {
+            name,
+            save: |world, entity| {
+                let relations = world.get::<Relations<R>>(entity)?;
+                Some(value::to_value(&relations.iter().collect::<Vec<_>>()))
+            },
+            load: |world, source, value| {
+                for (target, relation) in value::from_value::<Vec<(Entity, R)>>(value)? {
+                    if !world.is_alive(target) {
+                        return Err(serde::de::Error::custom(format!(
+                            "relation target {:?} is not alive",
+                            target
+                        )));
+                    }
+                    world.add_relation(source, relation, target);
+                }
+                Ok(())
+            },
+        })
+    }
+
+    fn push_component(&mut self, registration: ComponentRegistration) -> &mut Self {
+        assert!(
+            self.components
+                .iter()
+                .all(|other| other.name != registration.name),
+            "component {} is already registered",
+            registration.name
+        );
+        self.components.push(registration);
+        self
+    }
+
+    /// Registers the resource type under `name`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a resource type is already registered under `name`.
+    pub fn register_resource<R: Serialize + DeserializeOwned + Send + Sync + 'static>(
+        &mut self,
+        name: &'static str,
+    ) -> &mut Self {
+        assert!(
+            self.resources.iter().all(|other| other.name != name),
+            "resource {} is already registered",
+            name
+        );
+        self.resources.push(ResourceRegistration {
+            name,
+            save: |world| Some(value::to_value(&*world.resource::<R>()?)),
+            load: |world, value| {
+                world.insert_resource(value::from_value::<R>(value)?);
+                Ok(())
+            },
+        });
+        self
+    }
+
+    /// Saves every entity, and the values of the registered components and resources.
+    pub fn save(&self, world: &World) -> Result<SavedWorld, RegistryError> {
+        let slots: Vec<(Entity, bool)> = world.entities.slots().collect();
+        let mut entities = Vec::new();
+        for &(entity, alive) in &slots {
+            if !alive {
+                continue;
+            }
+            let mut components = Vec::new();
+            for registration in &self.components {
+                if let Some(value) = (registration.save)(world, entity) {
+                    let value =
+                        value.map_err(|err| RegistryError::value(registration.name, err))?;
+                    components.push((registration.name.to_string(), value));
+                }
+            }
+            entities.push(SavedEntity { entity, components });
+        }
+
+        let mut resources = Vec::new();
+        for registration in &self.resources {
+            if let Some(value) = (registration.save)(world) {
+                let value = value.map_err(|err| RegistryError::value(registration.name, err))?;
+                resources.push((registration.name.to_string(), value));
+            }
+        }
+        Ok(SavedWorld {
+            slots,
+            entities,
+            resources,
+        })
+    }
+
+    /// Creates a world with the saved entities, under their saved handles, and values.
+    ///
+    /// No component hooks run while the saved components are inserted. `Children` are then
+    /// rebuilt from the loaded `Parent`s, whether or not `Children` was registered, and relations
+    /// registered with `register_relation` come with their `RelationSources`. Hooks run as usual
+    /// for changes made to the loaded world.
+    pub fn load(&self, saved: &SavedWorld) -> Result<World, RegistryError> {
+        // Slots are restored by position, so they have to be listed in order
+        for (index, &(entity, _)) in saved.slots.iter().enumerate() {
+            if entity.index() as usize != index {
+                return Err(RegistryError::InvalidEntity(entity));
+            }
+        }
+
+        let mut world = World::new();
+        // Alive entities start without components, like those spawned by `new_entity`
+        let empty = world.archetypes.get_mut(0);
+        world
+            .entities
+            .restore(saved.slots.iter().copied(), |entity| EntityLocation {
+                archetype: 0,
+                row: empty.push(entity),
+            });
+
+        let hooks = mem::take(&mut world.hooks);
+        let builtin_hooks = mem::take(&mut world.builtin_hooks);
+        for saved_entity in &saved.entities {
+            let entity = saved_entity.entity;
+            if !world.is_alive(entity) {
+                return Err(RegistryError::InvalidEntity(entity));
+            }
+            for (name, value) in &saved_entity.components {
+                let registration = self
+                    .components
+                    .iter()
+                    .find(|registration| registration.name == name)
+                    .ok_or_else(|| RegistryError::NotRegistered(name.clone()))?;
+                (registration.load)(&mut world, entity, value)
+                    .map_err(|err| RegistryError::value(name, err))?;
+            }
+        }
+        hierarchy::rebuild_children(&mut world);
+        // Relations register their hooks as they load, next to those taken out above
+        world.hooks.extend(hooks);
+        world.builtin_hooks.extend(builtin_hooks);
+
+        for (name, value) in &saved.resources {
+            let registration = self
+                .resources
+                .iter()
+                .find(|registration| registration.name == name)
+                .ok_or_else(|| RegistryError::NotRegistered(name.clone()))?;
+            (registration.load)(&mut world, value)
+                .map_err(|err| RegistryError::value(name, err))?;
+        }
+        Ok(world)
+    }
+}
+
+/// Entities and resources saved from a world by `TypeRegistry::save`, ready to be written with
+/// any serde format.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SavedWorld {
+    // Every slot of the world, so that handles to despawned entities stay stale after loading
+    slots: Vec<(Entity, bool)>,
+    entities: Vec<SavedEntity>,
+    resources: Vec<(String, Value)>,
+}
+
+impl SavedWorld {
+    pub fn entities(&self) -> &[SavedEntity] {
+        &self.entities
+    }
+
+    /// Names of the saved resources.
+    pub fn resources(&self) -> impl Iterator<Item = &str> + '_ {
+        self.resources.iter().map(|(name, _)| name.as_str())
+    }
+}
+
+/// An entity of a `SavedWorld`, with its registered components.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SavedEntity {
+    entity: Entity,
+    components: Vec<(String, Value)>,
+}
+
+impl SavedEntity {
+    pub fn entity(&self) -> Entity {
+        self.entity
+    }
+
+    /// Names of the saved components.
+    pub fn components(&self) -> impl Iterator<Item = &str> + '_ {
+        self.components.iter().map(|(name, _)| name.as_str())
+    }
+}
+
+/// Error returned by `TypeRegistry::save` and `TypeRegistry::load`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RegistryError {
+    /// A saved world has a value under a name that no type is registered under.
+    NotRegistered(String),
+    /// The value saved under the name could not be converted to or from the type registered
+    /// under it.
+    InvalidValue { name: String, message: String },
+    /// A saved entity is not alive in the saved slots, or the slots are out of order.
+    InvalidEntity(Entity),
+}
+
+impl RegistryError {
+    fn value(name: &str, err: ValueError) -> Self {
+        RegistryError::InvalidValue {
+            name: name.to_string(),
+            message: err.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegistryError::NotRegistered(name) => write!(f, "no type is registered as {}", name),
+            RegistryError::InvalidValue { name, message } => {
+                write!(f, "invalid value for {}: {}", name, message)
+            }
+            RegistryError::InvalidEntity(entity) => {
+                write!(f, "entity {:?} does not match the saved slots", entity)
+            }
+        }
+    }
+}
+
+impl Error for RegistryError {}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use crate::{
+        Children, Component, Entity, Parent, RegistryError, Relation, SavedWorld, StorageType,
+        TypeRegistry, World,
+    };
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Health(i32);
+    impl Component for Health {}
+    #[derive(Serialize, Deserialize)]
+    struct Target(Entity);
+    impl Component for Target {
+        const STORAGE: StorageType = StorageType::SparseSet;
+    }
+    struct Unregistered;
+    impl Component for Unregistered {}
+    #[derive(Serialize, Deserialize)]
+    struct Wave(u32);
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Likes {
+        Little,
+        Lots { since: u32 },
+    }
+    impl Relation for Likes {}
+
+    fn registry() -> TypeRegistry {
+        let mut registry = TypeRegistry::new();
+        registry
+            .register_component::<Health>("Health")
+            .register_component::<Target>("Target")
+            .register_resource::<Wave>("Wave");
+        registry
+    }
+
+    fn through_ron(saved: &SavedWorld) -> SavedWorld {
+        ron::from_str(&ron::to_string(saved).unwrap()).unwrap()
+    }
+
+    fn through_bincode(saved: &SavedWorld) -> SavedWorld {
+        bincode::deserialize(&bincode::serialize(saved).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn worlds_round_trip_with_their_handles() {
+        let mut world = World::new();
+        let despawned = world.spawn(Health(0));
+        let enemy = world.spawn((Health(10), Unregistered));
+        world.despawn(despawned);
+        let recycled = world.spawn(Health(5));
+        let turret = world.spawn(Target(enemy));
+        world.insert_resource(Wave(3));
+
+        let registry = registry();
+        let saved = registry.save(&world).unwrap();
+        assert_eq!(saved.entities().len(), 3);
+        assert_eq!(saved.resources().collect::<Vec<_>>(), vec!["Wave"]);
+        let components: Vec<&str> = saved.entities()[1].components().collect();
+        assert_eq!(components, vec!["Health"]);
+
+        for written in [through_ron(&saved), through_bincode(&saved)] {
+            assert_eq!(written, saved);
+            let mut loaded = registry.load(&written).unwrap();
+            assert_eq!(loaded.get::<Target>(turret).unwrap().0, enemy);
+            assert_eq!(*loaded.get::<Health>(enemy).unwrap(), Health(10));
+            assert_eq!(*loaded.get::<Health>(recycled).unwrap(), Health(5));
+            assert!(!loaded.has::<Unregistered>(enemy));
+            assert!(!loaded.is_alive(despawned));
+            assert_eq!(loaded.resource::<Wave>().unwrap().0, 3);
+            assert_eq!(loaded.query::<&Health>().iter().count(), 2);
+
+            // New entities get handles that the saved world could not have handed out yet
+            let spawned = loaded.new_entity();
+            assert!(!world.is_alive(spawned) && spawned != despawned);
+        }
+    }
+
+    #[test]
+    fn hierarchies_are_rebuilt_from_parents() {
+        let mut with_children = registry();
+        with_children
+            .register_component::<Parent>("Parent")
+            .register_component::<Children>("Children");
+        let mut parents_only = registry();
+        parents_only.register_component::<Parent>("Parent");
+
+        let mut world = World::new();
+        let [parent, a, b] = [(); 3].map(|_| world.new_entity());
+        world.set_parent(b, parent);
+        world.set_parent(a, parent);
+
+        for registry in [with_children, parents_only] {
+            let saved = through_ron(&registry.save(&world).unwrap());
+            let mut loaded = registry.load(&saved).unwrap();
+            let children = loaded.children(parent).unwrap().to_vec();
+            assert_eq!(children.len(), 2);
+            assert!(children.contains(&a) && children.contains(&b));
+            loaded.despawn(a);
+            assert_eq!(loaded.children(parent).unwrap().to_vec(), vec![b]);
+        }
+    }
+
+    #[test]
+    fn relations_load_with_their_sources() {
+        let mut registry = registry();
+        registry.register_relation::<Likes>("Likes");
+        let mut world = World::new();
+        let [alice, bob, carol] = [(); 3].map(|_| world.new_entity());
+        world.add_relation(alice, Likes::Little, bob);
+        world.add_relation(carol, Likes::Lots { since: 3 }, bob);
+
+        let saved = through_bincode(&registry.save(&world).unwrap());
+        let mut loaded = registry.load(&saved).unwrap();
+        assert_eq!(
+            *loaded.relation::<Likes>(carol, bob).unwrap(),
+            Likes::Lots { since: 3 }
+        );
+        assert_eq!(loaded.sources::<Likes>(bob), vec![alice, carol]);
+        loaded.despawn(bob);
+        assert!(loaded.targets::<Likes>(alice).is_empty());
+    }
+
+    #[test]
+    fn loading_unregistered_types_fails() {
+        let mut world = World::new();
+        world.spawn(Health(1));
+        let saved = registry().save(&world).unwrap();
+        assert_eq!(
+            TypeRegistry::new().load(&saved).err(),
+            Some(RegistryError::NotRegistered("Health".to_string()))
+        );
+
+        // A value of another type under a registered name fails too
+        let mut other = TypeRegistry::new();
+        other.register_component::<Target>("Health");
+        assert!(matches!(
+            other.load(&saved),
+            Err(RegistryError::InvalidValue { .. })
+        ));
+    }
+}
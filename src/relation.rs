@@ -0,0 +1,393 @@
+use std::any::TypeId;
+
+use crate::cell::Ref;
+use crate::{Component, Entity, StorageType, World};
+
+/// Kind of relation between two entities, such as `Likes` or `Targets`. The value stored with
+/// each pair is the relation itself, so kinds can carry data.
+pub trait Relation: Send + Sync + 'static {}
+
+/// Relations of kind `R` from an entity to its targets, one pair per target.
+///
+/// Every pair is keyed by the relation type and the target entity. As a component, it lets
+/// queries match entities by relation kind whatever the target, e.g.
+/// `Query<(Entity, &Relations<Targets>)>` for all entities that target anything. The pairs are
+/// managed with `World::add_relation` and `World::remove_relation`, and pairs whose target is
+/// despawned are removed along with it.
+///
+/// ```
+/// use becs::{Entity, Query, Relation, Relations, World};
+///
+/// struct Targets;
+/// impl Relation for Targets {}
+///
+/// let mut world = World::new();
+/// let [turret, enemy, other] = [(); 3].map(|_| world.new_entity());
+/// world.add_relation(turret, Targets, enemy);
+/// world.add_relation(turret, Targets, other);
+///
+/// let targeting: Vec<Entity> = world
+///     .query::<(Entity, &Relations<Targets>)>()
+///     .iter()
+///     .map(|(entity, _)| entity)
+///     .collect();
+/// assert_eq!(targeting, vec![turret]);
+///
+/// world.despawn(enemy);
+/// assert_eq!(world.targets::<Targets>(turret), vec![other]);
+/// ```
+pub struct Relations<R> {
+    pairs: Vec<(Entity, R)>,
+}
+
+impl<R> Relations<R> {
+    pub fn get(&self, target: Entity) -> Option<&R> {
+        self.pairs
+            .iter()
+            .find(|(other, _)| *other == target)
+            .map(|(_, relation)| relation)
+    }
+
+    pub fn targets(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.pairs.iter().map(|&(target, _)| target)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, &R)> {
+        self.pairs
+            .iter()
+            .map(|(target, relation)| (*target, relation))
+    }
+
+    pub fn len(&self) -> usize {
+        self.pairs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pairs.is_empty()
+    }
+}
+
+impl<R: Relation> Component for Relations<R> {}
+
+type Unlink = fn(&mut World, Entity, Entity);
+
+/// Entities related to this entity, with a relation of any kind: the reverse side of
+/// `Relations`, kept in sync with it by `World::add_relation` and `World::remove_relation`.
+///
+/// It is read-only, and lets systems find everything related to an entity through a query:
+///
+/// ```
+/// use becs::{Entity, Query, Relation, RelationSources, Schedule, World};
+///
+/// struct Targets;
+/// impl Relation for Targets {}
+///
+/// fn report_threats(mut targeted: Query<(Entity, &RelationSources)>) {
+///     for (target, sources) in targeted.iter() {
+///         let threats: Vec<Entity> = sources.of::<Targets>().collect();
+///         println!("{:?} is targeted by {:?}", target, threats);
+///     }
+/// }
+///
+/// let mut world = World::new();
+/// let [turret, enemy] = [(); 2].map(|_| world.new_entity());
+/// world.add_relation(turret, Targets, enemy);
+///
+/// let mut schedule = Schedule::new();
+/// schedule.add_stage("update").add_system_to_stage("update", report_threats);
+/// schedule.run(&mut world);
+/// let sources = world.get::<RelationSources>(enemy).unwrap();
+/// assert_eq!(sources.iter().collect::<Vec<_>>(), vec![turret]);
+/// ```
+// Sparse so that becoming a target does not move the entity
+pub struct RelationSources {
+    sources: Vec<(TypeId, Entity, Unlink)>,
+}
+
+impl RelationSources {
+    /// Entities related to this one with a relation of any kind, each once.
+    pub fn iter(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.sources
+            .iter()
+            .enumerate()
+            .filter(move |&(index, &(_, source, _))| {
+                self.sources[..index]
+                    .iter()
+                    .all(|&(_, other, _)| other != source)
+            })
+            .map(|(_, &(_, source, _))| source)
+    }
+
+    /// Entities related to this one with `R`.
+    pub fn of<R: Relation>(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.sources
+            .iter()
+            .filter(|&&(kind, _, _)| kind == TypeId::of::<R>())
+            .map(|&(_, source, _)| source)
+    }
+}
+
+impl Component for RelationSources {
+    const STORAGE: StorageType = StorageType::SparseSet;
+}
+
+// Like the hierarchy hooks, each side is emptied before its component is removed, so the hooks
+// run on that removal find nothing left to unlink
+fn unlink_source<R: Relation>(world: &mut World, source: Entity, target: Entity) {
+    let now_empty = match world.get_mut::<Relations<R>>(source) {
+        Some(mut relations) => {
+            relations.pairs.retain(|&(other, _)| other != target);
+            relations.is_empty()
+        }
+        None => false,
+    };
+    if now_empty {
+        world.remove_component::<Relations<R>>(source);
+    }
+}
+
+fn unlink_target(world: &mut World, type_id: TypeId, source: Entity, target: Entity) {
+    let now_empty = match world.get_mut::<RelationSources>(target) {
+        Some(mut sources) => {
+            sources
+                .sources
+                .retain(|&(kind, other, _)| (kind, other) != (type_id, source));
+            sources.sources.is_empty()
+        }
+        None => false,
+    };
+    if now_empty {
+        world.remove_component::<RelationSources>(target);
+    }
+}
+
+// A self-relation's component on one side may already be gone when the hook of the other
+// runs, as unlinking it emptied and removed it
+fn unlink_targets<R: Relation>(world: &mut World, source: Entity) {
+    let Some(targets) = world
+        .get::<Relations<R>>(source)
+        .map(|relations| relations.targets().collect::<Vec<_>>())
+    else {
+        return;
+    };
+    for target in targets {
+        unlink_target(world, TypeId::of::<R>(), source, target);
+    }
+}
+
+fn unlink_sources(world: &mut World, target: Entity) {
+    let Some(sources) = world
+        .get::<RelationSources>(target)
+        .map(|sources| sources.sources.clone())
+    else {
+        return;
+    };
+    for (_, source, unlink) in sources {
+        unlink(world, source, target);
+    }
+}
+
+impl World {
+    /// Relates `source` to `target` with `relation`, replacing the relation of the same kind
+    /// between them.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either entity is dead.
+    pub fn add_relation<R: Relation>(&mut self, source: Entity, relation: R, target: Entity) {
+        assert!(self.is_alive(source), "source {:?} is not alive", source);
+        assert!(self.is_alive(target), "target {:?} is not alive", target);
+        // Built-in hooks, so they are set once per kind and never replace those of users
+        if !self
+            .builtin_hooks
+            .contains_key(&TypeId::of::<Relations<R>>())
+        {
+            self.register_builtin_hooks::<Relations<R>>()
+                .on_remove(unlink_targets::<R>);
+            self.register_builtin_hooks::<RelationSources>()
+                .on_remove(unlink_sources);
+        }
+
+        let mut relation = Some(relation);
+        if let Some(mut relations) = self.get_mut::<Relations<R>>(source) {
+            let replaced = relations
+                .pairs
+                .iter_mut()
+                .find(|(other, _)| *other == target);
+            if let Some((_, existing)) = replaced {
+                *existing = relation.take().unwrap();
+                return;
+            }
+            relations.pairs.push((target, relation.take().unwrap()));
+        }
+        if let Some(relation) = relation {
+            let pairs = vec![(target, relation)];
+            self.add_component_to_entity(source, Relations { pairs });
+        }
+
+        let link = (TypeId::of::<R>(), source, unlink_source::<R> as Unlink);
+        if let Some(mut sources) = self.get_mut::<RelationSources>(target) {
+            sources.sources.push(link);
+            return;
+        }
+        let sources = vec![link];
+        self.add_component_to_entity(target, RelationSources { sources });
+    }
+
+    /// Removes the relation of kind `R` from `source` to `target`, returning it.
+    pub fn remove_relation<R: Relation>(&mut self, source: Entity, target: Entity) -> Option<R> {
+        let (relation, now_empty) = {
+            let mut relations = self.get_mut::<Relations<R>>(source)?;
+            let index = relations
+                .pairs
+                .iter()
+                .position(|(other, _)| *other == target)?;
+            let (_, relation) = relations.pairs.remove(index);
+            (relation, relations.is_empty())
+        };
+        if now_empty {
+            self.remove_component::<Relations<R>>(source);
+        }
+        unlink_target(self, TypeId::of::<R>(), source, target);
+        Some(relation)
+    }
+
+    /// The relation of kind `R` from `source` to `target`, if there is one.
+    pub fn relation<R: Relation>(&self, source: Entity, target: Entity) -> Option<Ref<'_, R>> {
+        let relations = self.get::<Relations<R>>(source)?;
+        Ref::filter_map(relations, |relations| relations.get(target)).ok()
+    }
+
+    /// Entities that `source` relates to with `R`, in the order the relations were added.
+    pub fn targets<R: Relation>(&self, source: Entity) -> Vec<Entity> {
+        self.get::<Relations<R>>(source)
+            .map_or_else(Vec::new, |relations| relations.targets().collect())
+    }
+
+    /// Entities related to `target` with `R`.
+    pub fn sources<R: Relation>(&self, target: Entity) -> Vec<Entity> {
+        self.get::<RelationSources>(target)
+            .map_or_else(Vec::new, |sources| sources.of::<R>().collect())
+    }
+
+    /// Entities related to `target` with a relation of any kind.
+    pub fn all_sources(&self, target: Entity) -> Vec<Entity> {
+        self.get::<RelationSources>(target)
+            .map_or_else(Vec::new, |sources| sources.iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use crate::{Entity, Query, Relation, RelationSources, Relations, Schedule, With, World};
+
+    #[derive(Debug, PartialEq)]
+    struct Likes(u32);
+    impl Relation for Likes {}
+    struct Targets;
+    impl Relation for Targets {}
+
+    #[test]
+    fn pairs_are_keyed_by_kind_and_target() {
+        let mut world = World::new();
+        let [alice, bob, carol] = [(); 3].map(|_| world.new_entity());
+        world.add_relation(alice, Likes(1), bob);
+        world.add_relation(alice, Likes(2), carol);
+        world.add_relation(alice, Likes(3), bob);
+        world.add_relation(carol, Likes(4), bob);
+        world.add_relation(carol, Targets, alice);
+        world.add_relation(bob, Targets, alice);
+
+        assert_eq!(*world.relation::<Likes>(alice, bob).unwrap(), Likes(3));
+        assert!(world.relation::<Likes>(bob, alice).is_none());
+        assert_eq!(world.targets::<Likes>(alice), vec![bob, carol]);
+        assert_eq!(world.sources::<Likes>(bob), vec![alice, carol]);
+        assert_eq!(world.sources::<Targets>(bob), Vec::<Entity>::new());
+        assert_eq!(world.all_sources(alice), vec![carol, bob]);
+
+        let targeting: Vec<Entity> = world
+            .query_filtered::<Entity, With<Relations<Targets>>>()
+            .iter()
+            .collect();
+        assert_eq!(targeting, vec![carol, bob]);
+
+        assert_eq!(world.remove_relation::<Likes>(alice, bob), Some(Likes(3)));
+        assert_eq!(world.remove_relation::<Likes>(alice, bob), None);
+        assert_eq!(world.sources::<Likes>(bob), vec![carol]);
+        assert_eq!(world.remove_relation::<Likes>(alice, carol), Some(Likes(2)));
+        assert!(!world.has::<Relations<Likes>>(alice));
+    }
+
+    #[test]
+    fn despawning_either_side_removes_pairs() {
+        let mut world = World::new();
+        let [turret, enemy, other] = [(); 3].map(|_| world.new_entity());
+        world.add_relation(turret, Targets, enemy);
+        world.add_relation(other, Targets, enemy);
+        world.add_relation(turret, Likes(0), other);
+
+        world.despawn(enemy);
+        assert!(!world.has::<Relations<Targets>>(turret));
+        assert!(!world.has::<Relations<Targets>>(other));
+        assert_eq!(world.targets::<Likes>(turret), vec![other]);
+
+        world.despawn(turret);
+        assert!(world.all_sources(other).is_empty());
+    }
+
+    #[test]
+    fn entities_may_relate_to_themselves() {
+        let mut world = World::new();
+        let [narcissus, other] = [(); 2].map(|_| world.new_entity());
+        world.add_relation(narcissus, Likes(1), narcissus);
+        world.add_relation(narcissus, Likes(2), other);
+        assert_eq!(world.sources::<Likes>(narcissus), vec![narcissus]);
+
+        assert!(world.despawn(narcissus));
+        assert!(world.all_sources(other).is_empty());
+    }
+
+    #[test]
+    fn user_hooks_on_relations_are_kept() {
+        struct Unrelated(u32);
+
+        let mut world = World::new();
+        world.insert_resource(Unrelated(0));
+        world
+            .register_component_hooks::<Relations<Likes>>()
+            .on_remove(|world, _| world.resource_mut::<Unrelated>().unwrap().0 += 1);
+        let [alice, bob] = [(); 2].map(|_| world.new_entity());
+        world.add_relation(alice, Likes(1), bob);
+
+        world.despawn(bob);
+        assert!(!world.has::<Relations<Likes>>(alice));
+        assert_eq!(world.resource::<Unrelated>().unwrap().0, 1);
+    }
+
+    #[test]
+    fn systems_find_everything_related_to_an_entity() {
+        let found = Arc::new(Mutex::new(Vec::new()));
+        let recorded = found.clone();
+        let mut world = World::new();
+        let [alice, bob, carol] = [(); 3].map(|_| world.new_entity());
+        world.add_relation(alice, Likes(1), carol);
+        world.add_relation(bob, Targets, carol);
+        world.add_relation(bob, Likes(2), carol);
+        world.add_relation(carol, Targets, alice);
+
+        let mut schedule = Schedule::new();
+        schedule.add_stage("update").add_system_to_stage(
+            "update",
+            move |mut sources: Query<&RelationSources>| {
+                let sources = sources.get(carol).unwrap();
+                let all: Vec<Entity> = sources.iter().collect();
+                let targeting: Vec<Entity> = sources.of::<Targets>().collect();
+                recorded.lock().unwrap().push((all, targeting));
+            },
+        );
+        schedule.run(&mut world);
+        assert_eq!(*found.lock().unwrap(), vec![(vec![alice, bob], vec![bob])]);
+    }
+}
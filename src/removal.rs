@@ -0,0 +1,144 @@
+use std::marker::PhantomData;
+
+use crate::{Access, Component, Entity, SystemParam, World};
+
+/// Entities whose `T` was removed, or that were despawned with one, since the system last ran.
+///
+/// Removals are recorded by `World::remove_component` and `World::despawn`, including those
+/// applied from `Commands`, and are forgotten after the frame following the one they happened
+/// in. Systems that run less often than once per frame may miss some.
+///
+/// ```
+/// use becs::{Component, RemovedComponents, Schedule, World};
+///
+/// struct Body(u32);
+/// impl Component for Body {}
+///
+/// fn free_bodies(removed: RemovedComponents<Body>) {
+///     for entity in removed.iter() {
+///         println!("freeing the physics body of {:?}", entity);
+///     }
+/// }
+///
+/// let mut schedule = Schedule::new();
+/// schedule.add_stage("cleanup").add_system_to_stage("cleanup", free_bodies);
+///
+/// let mut world = World::new();
+/// let entity = world.spawn(Body(7));
+/// world.despawn(entity);
+/// assert_eq!(world.removed::<Body>().collect::<Vec<_>>(), vec![entity]);
+/// schedule.run(&mut world);
+/// ```
+pub struct RemovedComponents<'w, T> {
+    world: &'w World,
+    last_run: u32,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<'w, T: Component> RemovedComponents<'w, T> {
+    pub fn iter(&self) -> impl Iterator<Item = Entity> + 'w {
+        self.world.removed_since::<T>(self.last_run)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.iter().next().is_none()
+    }
+}
+
+// The log is only written through `&mut World`, so reading it borrows nothing while systems run
+impl<T: Component> SystemParam for RemovedComponents<'_, T> {
+    type State = ();
+    type Item<'w, 's> = RemovedComponents<'w, T>;
+
+    fn init_state(_world: &mut World, _access: &mut Access) -> Self::State {}
+
+    fn get_param<'w, 's>(
+        _state: &'s mut Self::State,
+        world: &'w World,
+        last_run: u32,
+        _this_run: u32,
+    ) -> Self::Item<'w, 's> {
+        RemovedComponents {
+            world,
+            last_run,
+            marker: PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use crate::{Commands, Component, Entity, Query, RemovedComponents, Schedule, With, World};
+
+    struct Body(u32);
+    impl Component for Body {}
+    struct Expired;
+    impl Component for Expired {}
+
+    #[test]
+    fn world_records_removals_and_despawns() {
+        let mut world = World::new();
+        let entities = world.spawn_batch((0..4).map(Body));
+        assert_eq!(world.removed::<Body>().count(), 0);
+
+        world.remove_component::<Body>(entities[1]);
+        world.despawn(entities[3]);
+        world.despawn(entities[2]);
+        // Neither the failed removal nor the despawn of an entity without the component count
+        world.remove_component::<Body>(entities[1]);
+        let empty = world.new_entity();
+        world.despawn(empty);
+        assert_eq!(
+            world.removed::<Body>().collect::<Vec<_>>(),
+            vec![entities[1], entities[3], entities[2]]
+        );
+
+        world.clear_trackers();
+        assert_eq!(world.removed::<Body>().count(), 0);
+    }
+
+    #[test]
+    fn systems_see_each_removal_once() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let before = seen.clone();
+        let after = seen.clone();
+        let mut schedule = Schedule::new();
+        schedule
+            .add_stage("first")
+            .add_stage("expire")
+            .add_stage("last")
+            .add_system_to_stage("first", move |removed: RemovedComponents<Body>| {
+                before
+                    .lock()
+                    .unwrap()
+                    .extend(removed.iter().map(|e| ("first", e)));
+            })
+            .add_system_to_stage(
+                "expire",
+                |mut commands: Commands, mut expired: Query<Entity, With<Expired>>| {
+                    for entity in expired.iter() {
+                        commands.despawn(entity);
+                    }
+                },
+            )
+            .add_system_to_stage("last", move |removed: RemovedComponents<Body>| {
+                after
+                    .lock()
+                    .unwrap()
+                    .extend(removed.iter().map(|e| ("last", e)));
+            });
+
+        let mut world = World::new();
+        let entity = world.spawn((Body(1), Expired));
+        schedule.run(&mut world);
+        assert_eq!(*seen.lock().unwrap(), vec![("last", entity)]);
+        schedule.run(&mut world);
+        schedule.run(&mut world);
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![("last", entity), ("first", entity)]
+        );
+    }
+}
@@ -0,0 +1,249 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::ops::{Deref, DerefMut};
+
+use crate::cell::{AtomicRefCell, Ref, RefMut};
+use crate::system::add_param_access;
+use crate::{Access, SystemParam, World};
+
+type BoxedResource = Box<dyn Any + Send + Sync>;
+
+/// Singletons of any `Send + Sync` type, keyed by type.
+#[derive(Default)]
+pub(crate) struct Resources {
+    resources: HashMap<TypeId, AtomicRefCell<BoxedResource>>,
+}
+
+impl Resources {
+    /// Inserts the resource, returning the one it replaces.
+    pub fn insert<R: Send + Sync + 'static>(&mut self, resource: R) -> Option<R> {
+        let previous = self
+            .resources
+            .insert(TypeId::of::<R>(), AtomicRefCell::new(Box::new(resource)))?;
+        Some(*downcast(previous))
+    }
+
+    pub fn remove<R: Send + Sync + 'static>(&mut self) -> Option<R> {
+        let resource = self.resources.remove(&TypeId::of::<R>())?;
+        Some(*downcast(resource))
+    }
+
+    pub fn contains<R: 'static>(&self) -> bool {
+        self.resources.contains_key(&TypeId::of::<R>())
+    }
+
+    pub fn get<R: 'static>(&self) -> Option<&AtomicRefCell<BoxedResource>> {
+        self.resources.get(&TypeId::of::<R>())
+    }
+}
+
+fn downcast<R: 'static>(mut resource: AtomicRefCell<BoxedResource>) -> Box<R> {
+    // Resources are only ever inserted under their own type id
+    std::mem::replace(resource.get_mut(), Box::new(()))
+        .downcast()
+        .unwrap_or_else(|_| unreachable!())
+}
+
+/// Shared borrow of the resource `R`, usable as a system parameter.
+///
+/// Systems taking `Res<R>` panic if the world has no `R`. Take `Option<Res<R>>` for resources
+/// that may be missing.
+pub struct Res<'w, R> {
+    value: Ref<'w, R>,
+}
+
+impl<R> Deref for Res<'_, R> {
+    type Target = R;
+
+    fn deref(&self) -> &R {
+        &self.value
+    }
+}
+
+/// Mutable borrow of the resource `R`, usable as a system parameter.
+///
+/// Systems taking `ResMut<R>` panic if the world has no `R`. Take `Option<ResMut<R>>` for
+/// resources that may be missing.
+pub struct ResMut<'w, R> {
+    value: RefMut<'w, R>,
+}
+
+impl<R> Deref for ResMut<'_, R> {
+    type Target = R;
+
+    fn deref(&self) -> &R {
+        &self.value
+    }
+}
+
+impl<R> DerefMut for ResMut<'_, R> {
+    fn deref_mut(&mut self) -> &mut R {
+        &mut self.value
+    }
+}
+
+// Resources are recorded in the access under the type of their parameter, so they never
+// conflict with components of the same type
+impl<R: Send + Sync + 'static> SystemParam for Option<Res<'_, R>> {
+    type State = ();
+    type Item<'w, 's> = Option<Res<'w, R>>;
+
+    fn init_state(_world: &mut World, access: &mut Access) -> Self::State {
+        let mut param_access = Access::new();
+        param_access.add_read::<Res<'static, R>>();
+        add_param_access::<Self>(access, &param_access);
+    }
+
+    fn get_param<'w, 's>(
+        _state: &'s mut Self::State,
+        world: &'w World,
+        _last_run: u32,
+        _this_run: u32,
+    ) -> Self::Item<'w, 's> {
+        world.resource::<R>().map(|value| Res { value })
+    }
+}
+
+impl<R: Send + Sync + 'static> SystemParam for Res<'_, R> {
+    type State = ();
+    type Item<'w, 's> = Res<'w, R>;
+
+    fn init_state(world: &mut World, access: &mut Access) -> Self::State {
+        Option::<Res<'_, R>>::init_state(world, access)
+    }
+
+    fn get_param<'w, 's>(
+        state: &'s mut Self::State,
+        world: &'w World,
+        last_run: u32,
+        this_run: u32,
+    ) -> Self::Item<'w, 's> {
+        Option::<Res<'_, R>>::get_param(state, world, last_run, this_run)
+            .unwrap_or_else(|| panic!("resource {} does not exist", std::any::type_name::<R>()))
+    }
+}
+
+impl<R: Send + Sync + 'static> SystemParam for Option<ResMut<'_, R>> {
+    type State = ();
+    type Item<'w, 's> = Option<ResMut<'w, R>>;
+
+    fn init_state(_world: &mut World, access: &mut Access) -> Self::State {
+        let mut param_access = Access::new();
+        param_access.add_write::<Res<'static, R>>();
+        add_param_access::<Self>(access, &param_access);
+    }
+
+    fn get_param<'w, 's>(
+        _state: &'s mut Self::State,
+        world: &'w World,
+        _last_run: u32,
+        _this_run: u32,
+    ) -> Self::Item<'w, 's> {
+        world.resource_mut::<R>().map(|value| ResMut { value })
+    }
+}
+
+impl<R: Send + Sync + 'static> SystemParam for ResMut<'_, R> {
+    type State = ();
+    type Item<'w, 's> = ResMut<'w, R>;
+
+    fn init_state(world: &mut World, access: &mut Access) -> Self::State {
+        Option::<ResMut<'_, R>>::init_state(world, access)
+    }
+
+    fn get_param<'w, 's>(
+        state: &'s mut Self::State,
+        world: &'w World,
+        last_run: u32,
+        this_run: u32,
+    ) -> Self::Item<'w, 's> {
+        Option::<ResMut<'_, R>>::get_param(state, world, last_run, this_run)
+            .unwrap_or_else(|| panic!("resource {} does not exist", std::any::type_name::<R>()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{BecsError, Component, IntoSystem, Query, Res, ResMut, Schedule, System, World};
+
+    struct Time(f32);
+    struct Score(u32);
+    impl Component for Score {}
+    struct Health(i32);
+    impl Component for Health {}
+
+    #[test]
+    fn insert_get_and_remove() {
+        let mut world = World::new();
+        assert!(world.resource::<Time>().is_none());
+        assert!(world.insert_resource(Time(0.5)).is_none());
+        assert!(world.contains_resource::<Time>());
+
+        world.resource_mut::<Time>().unwrap().0 += 1.0;
+        assert_eq!(world.resource::<Time>().unwrap().0, 1.5);
+        {
+            let _time = world.resource_mut::<Time>().unwrap();
+            assert!(matches!(
+                world.try_resource::<Time>(),
+                Err(BecsError::AlreadyBorrowed(_))
+            ));
+        }
+
+        assert_eq!(world.insert_resource(Time(2.0)).unwrap().0, 1.5);
+        assert_eq!(world.remove_resource::<Time>().unwrap().0, 2.0);
+        assert!(world.remove_resource::<Time>().is_none());
+        assert_eq!(
+            world.try_resource_mut::<Time>().err(),
+            Some(BecsError::ResourceMissing(std::any::type_name::<Time>()))
+        );
+    }
+
+    #[test]
+    fn systems_take_resources() {
+        let mut schedule = Schedule::new();
+        schedule
+            .add_stage("update")
+            .add_system_to_stage(
+                "update",
+                |time: Res<Time>, mut healths: Query<&mut Health>| {
+                    for mut health in healths.iter() {
+                        health.0 -= time.0 as i32;
+                    }
+                },
+            )
+            .add_system_to_stage(
+                "update",
+                |mut healths: Query<&Health>, score: Option<ResMut<Score>>| {
+                    if let Some(mut score) = score {
+                        score.0 += healths.iter().filter(|health| health.0 <= 0).count() as u32;
+                    }
+                },
+            );
+
+        let mut world = World::new();
+        world.insert_resource(Time(5.0));
+        let entity = world.new_entity();
+        world.add_component_to_entity(entity, Health(10));
+
+        schedule.run(&mut world);
+        assert!(!world.contains_resource::<Score>());
+        world.insert_resource(Score(0));
+        schedule.run(&mut world);
+        assert_eq!(world.resource::<Score>().unwrap().0, 1);
+    }
+
+    #[test]
+    fn resources_and_components_of_a_type_do_not_conflict() {
+        let mut system = (|_: Res<Score>, _: Query<&mut Score>| {}).into_system();
+        system.initialize(&mut World::new());
+    }
+
+    #[test]
+    #[should_panic]
+    fn missing_resource_panics() {
+        let mut system = (|_: Res<Time>| {}).into_system();
+        let mut world = World::new();
+        system.initialize(&mut world);
+        system.run(&world);
+    }
+}
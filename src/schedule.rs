@@ -0,0 +1,296 @@
+use crate::pool::ThreadPool;
+use crate::{IntoSystem, System, World};
+
+struct Stage {
+    name: &'static str,
+    systems: Vec<Box<dyn System>>,
+    // Batch of each system, computed from their access once they are initialized
+    batches: Option<Vec<usize>>,
+}
+
+impl Stage {
+    // Places every system in the first batch after all earlier systems it conflicts with, so
+    // conflicting systems still run in the order they were added
+    fn batches(&mut self) -> &[usize] {
+        let systems = &self.systems;
+        self.batches.get_or_insert_with(|| {
+            let mut batches: Vec<usize> = Vec::with_capacity(systems.len());
+            for (index, system) in systems.iter().enumerate() {
+                let batch = systems[..index]
+                    .iter()
+                    .zip(&batches)
+                    .filter(|(earlier, _)| !earlier.access().is_compatible(system.access()))
+                    .map(|(_, batch)| batch + 1)
+                    .max()
+                    .unwrap_or(0);
+                batches.push(batch);
+            }
+            batches
+        })
+    }
+}
+
+/// Ordered stages of systems, run against a `World` once per tick.
+///
+/// Stages run one after the other. Within a stage, systems whose access does not conflict run
+/// at the same time on worker threads, while conflicting systems run in the order they were
+/// added. At the end of each stage every system that ran gets to apply its deferred changes.
+///
+/// The worker threads, one per available core, are started the first time a batch has more
+/// than one system to run and live as long as the schedule.
+///
+/// ```
+/// use becs::{Component, Query, Schedule, World};
+///
+/// struct Health(i32);
+/// impl Component for Health {}
+///
+/// fn regenerate(mut healths: Query<&mut Health>) {
+///     for mut health in healths.iter() {
+///         health.0 += 1;
+///     }
+/// }
+///
+/// let mut schedule = Schedule::new();
+/// schedule.add_stage("update").add_system_to_stage("update", regenerate);
+///
+/// let mut world = World::new();
+/// let entity = world.new_entity();
+/// world.add_component_to_entity(entity, Health(10));
+/// schedule.run(&mut world);
+/// assert_eq!(world.get::<Health>(entity).unwrap().0, 11);
+/// ```
+#[derive(Default)]
+pub struct Schedule {
+    stages: Vec<Stage>,
+    pool: Option<ThreadPool>,
+}
+
+impl Schedule {
+    pub fn new() -> Self {
+        Schedule {
+            stages: Vec::new(),
+            pool: None,
+        }
+    }
+
+    /// Adds an empty stage that runs after the existing ones.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the schedule already has a stage with the name.
+    pub fn add_stage(&mut self, name: &'static str) -> &mut Self {
+        assert!(
+            self.stage_mut(name).is_none(),
+            "stage {} already exists",
+            name
+        );
+        self.stages.push(Stage {
+            name,
+            systems: Vec::new(),
+            batches: None,
+        });
+        self
+    }
+
+    /// Adds a system that runs after the systems already in the stage.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the schedule has no stage with the name.
+    pub fn add_system_to_stage<Marker>(
+        &mut self,
+        stage: &str,
+        system: impl IntoSystem<Marker>,
+    ) -> &mut Self {
+        let stage = self
+            .stage_mut(stage)
+            .unwrap_or_else(|| panic!("stage {} does not exist", stage));
+        stage.systems.push(Box::new(system.into_system()));
+        stage.batches = None;
+        self
+    }
+
+    pub fn stage_names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.stages.iter().map(|stage| stage.name)
+    }
+
+    /// Runs every stage once, then marks the end of the tick with `World::clear_trackers`.
+    pub fn run(&mut self, world: &mut World) {
+        for stage in &mut self.stages {
+            for system in &mut stage.systems {
+                system.initialize(world);
+            }
+
+            let batches = stage.batches().to_vec();
+            let batch_count = batches.iter().map(|batch| batch + 1).max().unwrap_or(0);
+            for batch in 0..batch_count {
+                let systems = stage
+                    .systems
+                    .iter_mut()
+                    .zip(&batches)
+                    .filter(|(_, system_batch)| **system_batch == batch)
+                    .map(|(system, _)| system);
+
+                // Each batch runs at its own tick so the systems after it see its changes
+                world.increment_change_tick();
+                run_batch(&mut self.pool, systems.collect(), world);
+            }
+            // Deferred changes get a tick of their own too, or the last batch would never see them
+            world.increment_change_tick();
+            for system in &mut stage.systems {
+                system.apply_deferred(world);
+            }
+        }
+        world.clear_trackers();
+    }
+
+    fn stage_mut(&mut self, name: &str) -> Option<&mut Stage> {
+        self.stages.iter_mut().find(|stage| stage.name == name)
+    }
+}
+
+// Runs systems that do not conflict with each other on the worker threads
+fn run_batch(pool: &mut Option<ThreadPool>, systems: Vec<&mut Box<dyn System>>, world: &World) {
+    if systems.len() > 1 {
+        let pool = pool.get_or_insert_with(ThreadPool::new);
+        if pool.threads() > 1 {
+            let tasks = systems
+                .into_iter()
+                .map(|system| Box::new(move || system.run(world)) as Box<dyn FnOnce() + Send + '_>)
+                .collect();
+            pool.scope(tasks);
+            return;
+        }
+    }
+    for system in systems {
+        system.run(world);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use crate::{Changed, Component, Entity, Query, Schedule, With, World};
+
+    struct Health(i32);
+    impl Component for Health {}
+    struct Poisoned;
+    impl Component for Poisoned {}
+
+    #[test]
+    fn stages_run_in_order() {
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let mut schedule = Schedule::new();
+        schedule.add_stage("update").add_stage("render");
+        for (stage, label) in [
+            ("render", "draw"),
+            ("update", "move"),
+            ("update", "collide"),
+        ] {
+            let order = order.clone();
+            schedule.add_system_to_stage(stage, move || order.lock().unwrap().push(label));
+        }
+
+        let mut world = World::new();
+        schedule.run(&mut world);
+        schedule.run(&mut world);
+        assert_eq!(
+            *order.lock().unwrap(),
+            vec!["move", "collide", "draw", "move", "collide", "draw"]
+        );
+        assert_eq!(
+            schedule.stage_names().collect::<Vec<_>>(),
+            vec!["update", "render"]
+        );
+    }
+
+    #[test]
+    fn later_systems_see_changes_of_earlier_ones() {
+        let damaged = Arc::new(Mutex::new(Vec::new()));
+        let recorded = damaged.clone();
+        let mut schedule = Schedule::new();
+        schedule
+            .add_stage("update")
+            .add_system_to_stage(
+                "update",
+                |mut healths: Query<&mut Health, With<Poisoned>>| {
+                    for mut health in healths.iter() {
+                        health.0 -= 1;
+                    }
+                },
+            )
+            .add_system_to_stage(
+                "update",
+                move |mut changed: Query<Entity, Changed<Health>>| {
+                    recorded.lock().unwrap().push(changed.iter().count());
+                },
+            );
+
+        let mut world = World::new();
+        for i in 0..3 {
+            let entity = world.new_entity();
+            world.add_component_to_entity(entity, Health(10));
+            if i == 0 {
+                world.add_component_to_entity(entity, Poisoned);
+            }
+        }
+        schedule.run(&mut world);
+        schedule.run(&mut world);
+
+        // Everything is new on the first run, afterwards only the poisoned entity changes
+        assert_eq!(*damaged.lock().unwrap(), vec![3, 1]);
+    }
+
+    #[test]
+    fn systems_without_conflicts_share_a_batch() {
+        struct Name(&'static str);
+        impl Component for Name {}
+
+        let mut schedule = Schedule::new();
+        schedule
+            .add_stage("update")
+            .add_system_to_stage("update", |_: Query<&mut Health>| {})
+            .add_system_to_stage("update", |_: Query<&mut Name>| {})
+            .add_system_to_stage("update", |_: Query<&Health>| {})
+            .add_system_to_stage("update", |_: Query<&Name, Changed<Health>>| {})
+            .add_system_to_stage("update", |_: Query<Entity, With<Health>>| {});
+
+        let mut world = World::new();
+        schedule.run(&mut world);
+        assert_eq!(schedule.stages[0].batches(), &[0, 0, 1, 1, 0]);
+    }
+
+    #[test]
+    fn batches_run_every_system() {
+        let mut schedule = Schedule::new();
+        schedule.add_stage("update");
+        for _ in 0..8 {
+            schedule.add_system_to_stage("update", |mut healths: Query<&Health>| {
+                assert_eq!(healths.iter().count(), 4);
+            });
+        }
+        schedule.add_system_to_stage("update", |mut healths: Query<&mut Health>| {
+            for mut health in healths.iter() {
+                health.0 += 1;
+            }
+        });
+
+        let mut world = World::new();
+        for _ in 0..4 {
+            let entity = world.new_entity();
+            world.add_component_to_entity(entity, Health(0));
+        }
+        for _ in 0..3 {
+            schedule.run(&mut world);
+        }
+        assert!(world.query::<&Health>().iter().all(|health| health.0 == 3));
+    }
+
+    #[test]
+    #[should_panic]
+    fn adding_to_missing_stage_panics() {
+        Schedule::new().add_system_to_stage("update", || {});
+    }
+}
@@ -0,0 +1,546 @@
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::archetype::EntityLocation;
+use crate::cell::{AtomicRefCell, Ref, RefMut};
+use crate::entity::Entities;
+use crate::{ComponentVec, Entity, Mut, StorageType};
+
+/// Ticks at which a component was added and last changed, compared against a query's last run
+/// to implement the `Added` and `Changed` filters.
+///
+/// They are kept per entity index, outside the cell holding the components, so a filter can
+/// read them while the same components are mutably borrowed by the query's data.
+pub(crate) struct ComponentTicks {
+    added: AtomicU32,
+    changed: AtomicU32,
+}
+
+impl ComponentTicks {
+    fn new(tick: u32) -> Self {
+        ComponentTicks {
+            added: AtomicU32::new(tick),
+            changed: AtomicU32::new(tick),
+        }
+    }
+
+    pub fn is_added(&self, last_run: u32) -> bool {
+        self.added.load(Ordering::Relaxed) > last_run
+    }
+
+    pub fn is_changed(&self, last_run: u32) -> bool {
+        self.changed.load(Ordering::Relaxed) > last_run
+    }
+
+    pub fn set_changed(&self, tick: u32) {
+        self.changed.store(tick, Ordering::Relaxed);
+    }
+}
+
+/// Sparse set keyed by entity index: the components are packed in `dense`, and `sparse` maps an
+/// entity index to its position there.
+pub(crate) struct SparseSet<T> {
+    sparse: Vec<Option<u32>>,
+    dense: Vec<T>,
+    indices: Vec<u32>,
+}
+
+impl<T> SparseSet<T> {
+    fn new() -> Self {
+        SparseSet {
+            sparse: Vec::new(),
+            dense: Vec::new(),
+            indices: Vec::new(),
+        }
+    }
+
+    fn get(&self, index: usize) -> Option<&T> {
+        let dense = (*self.sparse.get(index)?)?;
+        self.dense.get(dense as usize)
+    }
+
+    fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        let dense = (*self.sparse.get(index)?)?;
+        self.dense.get_mut(dense as usize)
+    }
+
+    fn insert(&mut self, index: usize, component: T) -> Option<T> {
+        if let Some(existing) = self.get_mut(index) {
+            return Some(std::mem::replace(existing, component));
+        }
+
+        if self.sparse.len() <= index {
+            self.sparse.resize(index + 1, None);
+        }
+        self.sparse[index] = Some(self.dense.len() as u32);
+        self.dense.push(component);
+        self.indices.push(index as u32);
+        None
+    }
+
+    fn remove(&mut self, index: usize) -> Option<T> {
+        let dense = self.sparse.get_mut(index)?.take()? as usize;
+        self.indices.swap_remove(dense);
+        if let Some(&moved) = self.indices.get(dense) {
+            self.sparse[moved as usize] = Some(dense as u32);
+        }
+        Some(self.dense.swap_remove(dense))
+    }
+}
+
+/// The components of one type, laid out according to the type's `StorageType`.
+pub(crate) enum Components<T> {
+    // One column per archetype, indexed by archetype id. Archetypes without the type keep an
+    // empty column.
+    Table(Vec<Vec<T>>),
+    SparseSet(SparseSet<T>),
+    Dense(Vec<Option<T>>),
+}
+
+impl<T> Components<T> {
+    fn new(storage_type: StorageType) -> Self {
+        match storage_type {
+            StorageType::Table => Components::Table(Vec::new()),
+            StorageType::SparseSet => Components::SparseSet(SparseSet::new()),
+            StorageType::Dense => Components::Dense(Vec::new()),
+        }
+    }
+
+    pub fn get(&self, entity: Entity, location: EntityLocation) -> Option<&T> {
+        match self {
+            Components::Table(columns) => columns.get(location.archetype)?.get(location.row),
+            Components::SparseSet(set) => set.get(entity.index() as usize),
+            Components::Dense(slots) => slots.get(entity.index() as usize)?.as_ref(),
+        }
+    }
+
+    pub fn get_mut(&mut self, entity: Entity, location: EntityLocation) -> Option<&mut T> {
+        match self {
+            Components::Table(columns) => {
+                columns.get_mut(location.archetype)?.get_mut(location.row)
+            }
+            Components::SparseSet(set) => set.get_mut(entity.index() as usize),
+            Components::Dense(slots) => slots.get_mut(entity.index() as usize)?.as_mut(),
+        }
+    }
+
+    /// Raw pointers to the components, to hand out mutable references to several components at
+    /// once while the components stay mutably borrowed.
+    pub fn ptr(&mut self) -> ComponentsPtr<T> {
+        match self {
+            Components::Table(columns) => ComponentsPtr::Table(
+                columns
+                    .iter_mut()
+                    .map(|column| (column.as_mut_ptr(), column.len()))
+                    .collect(),
+            ),
+            Components::SparseSet(set) => ComponentsPtr::SparseSet {
+                sparse: &set.sparse[..],
+                dense: set.dense.as_mut_ptr(),
+            },
+            Components::Dense(slots) => ComponentsPtr::Dense(slots.as_mut_ptr(), slots.len()),
+        }
+    }
+
+    fn columns(&mut self) -> &mut Vec<Vec<T>> {
+        match self {
+            Components::Table(columns) => columns,
+            _ => panic!("component is not stored in tables"),
+        }
+    }
+}
+
+fn column<T>(columns: &mut Vec<Vec<T>>, archetype: usize) -> &mut Vec<T> {
+    if columns.len() <= archetype {
+        columns.resize_with(archetype + 1, Vec::new);
+    }
+    &mut columns[archetype]
+}
+
+pub(crate) enum ComponentsPtr<T> {
+    Table(Vec<(*mut T, usize)>),
+    SparseSet {
+        sparse: *const [Option<u32>],
+        dense: *mut T,
+    },
+    Dense(*mut Option<T>, usize),
+}
+
+// The pointers stand in for the mutable borrow held alongside them, so they can cross threads
+// whenever `&mut T` could
+unsafe impl<T: Send> Send for ComponentsPtr<T> {}
+unsafe impl<T: Sync> Sync for ComponentsPtr<T> {}
+
+impl<T> ComponentsPtr<T> {
+    /// # Safety
+    ///
+    /// The components must still be mutably borrowed, and no other reference to the entity's
+    /// component may be alive.
+    pub unsafe fn get<'a>(&self, entity: Entity, location: EntityLocation) -> Option<&'a mut T> {
+        match *self {
+            ComponentsPtr::Table(ref columns) => {
+                let &(column, len) = columns.get(location.archetype)?;
+                if location.row >= len {
+                    return None;
+                }
+                Some(&mut *column.add(location.row))
+            }
+            ComponentsPtr::SparseSet { sparse, dense } => {
+                let dense_index = (*(&*sparse).get(entity.index() as usize)?)?;
+                Some(&mut *dense.add(dense_index as usize))
+            }
+            ComponentsPtr::Dense(slots, len) => {
+                let index = entity.index() as usize;
+                if index >= len {
+                    return None;
+                }
+                (*slots.add(index)).as_mut()
+            }
+        }
+    }
+}
+
+pub(crate) struct ComponentStorage<T> {
+    storage_type: StorageType,
+    pub components: AtomicRefCell<Components<T>>,
+    // Indexed by entity index, `None` for entities without the component
+    pub ticks: Vec<Option<ComponentTicks>>,
+    // Entities whose component was taken out, with the tick it happened at, oldest first
+    pub removed: Vec<(Entity, u32)>,
+}
+
+impl<T> ComponentStorage<T> {
+    pub fn new(storage_type: StorageType) -> Self {
+        ComponentStorage {
+            storage_type,
+            components: AtomicRefCell::new(Components::new(storage_type)),
+            ticks: Vec::new(),
+            removed: Vec::new(),
+        }
+    }
+
+    pub fn ticks(&self, entity: Entity) -> Option<&ComponentTicks> {
+        self.ticks.get(entity.index() as usize)?.as_ref()
+    }
+
+    fn set_added(&mut self, entity: Entity, tick: u32) {
+        let index = entity.index() as usize;
+        if self.ticks.len() <= index {
+            self.ticks.resize_with(index + 1, || None);
+        }
+        self.ticks[index] = Some(ComponentTicks::new(tick));
+    }
+
+    fn clear_ticks(&mut self, entity: Entity) {
+        if let Some(ticks) = self.ticks.get_mut(entity.index() as usize) {
+            *ticks = None;
+        }
+    }
+
+    /// Adds the component of an entity that does not have one yet, as the last row of the
+    /// archetype's column for table storage.
+    pub fn push(&mut self, entity: Entity, archetype: usize, component: T, tick: u32) {
+        self.set_added(entity, tick);
+        match self.components.get_mut() {
+            Components::Table(columns) => column(columns, archetype).push(component),
+            Components::SparseSet(set) => {
+                set.insert(entity.index() as usize, component);
+            }
+            Components::Dense(slots) => {
+                let index = entity.index() as usize;
+                if slots.len() <= index {
+                    slots.resize_with(index + 1, || None);
+                }
+                slots[index] = Some(component);
+            }
+        }
+    }
+
+    /// Replaces the component of an entity that already has one.
+    pub fn replace(&mut self, entity: Entity, location: EntityLocation, component: T, tick: u32) {
+        *self.components.get_mut().get_mut(entity, location).unwrap() = component;
+        self.ticks(entity).unwrap().set_changed(tick);
+    }
+
+    /// Takes the entity's component out, swap removing it from its column for table storage, and
+    /// records the removal at `tick`.
+    pub fn take(&mut self, entity: Entity, location: EntityLocation, tick: u32) -> Option<T> {
+        let component = match self.components.get_mut() {
+            Components::Table(columns) => {
+                let column = columns.get_mut(location.archetype)?;
+                if location.row >= column.len() {
+                    return None;
+                }
+                Some(column.swap_remove(location.row))
+            }
+            Components::SparseSet(set) => set.remove(entity.index() as usize),
+            Components::Dense(slots) => slots.get_mut(entity.index() as usize)?.take(),
+        };
+        self.clear_ticks(entity);
+        if component.is_some() {
+            self.removed.push((entity, tick));
+        }
+        component
+    }
+}
+
+impl<T: Send + Sync + 'static> ComponentVec for ComponentStorage<T> {
+    fn storage_type(&self) -> StorageType {
+        self.storage_type
+    }
+
+    fn move_row(&mut self, from: EntityLocation, to: usize) {
+        let columns = self.components.get_mut().columns();
+        let component = columns[from.archetype].swap_remove(from.row);
+        column(columns, to).push(component);
+    }
+
+    fn contains(&self, entity: Entity) -> bool {
+        self.ticks(entity).is_some()
+    }
+
+    fn remove(&mut self, entity: Entity, location: EntityLocation, tick: u32) {
+        self.take(entity, location, tick);
+    }
+
+    fn clear_removed(&mut self, before: u32) {
+        let stale = self.removed.partition_point(|&(_, tick)| tick <= before);
+        self.removed.drain(..stale);
+    }
+
+    fn reserve(&mut self, archetype: usize, additional: usize) {
+        self.ticks.reserve(additional);
+        match self.components.get_mut() {
+            Components::Table(columns) => column(columns, archetype).reserve(additional),
+            Components::SparseSet(set) => {
+                set.dense.reserve(additional);
+                set.indices.reserve(additional);
+            }
+            Components::Dense(slots) => slots.reserve(additional),
+        }
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self as &dyn std::any::Any
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self as &mut dyn std::any::Any
+    }
+}
+
+/// Borrowed view of every component of type `T`, indexed by entity like a `Vec<Option<T>>`.
+pub struct ComponentVecRef<'w, T> {
+    entities: &'w Entities,
+    components: Ref<'w, Components<T>>,
+}
+
+impl<'w, T> ComponentVecRef<'w, T> {
+    pub(crate) fn new(entities: &'w Entities, components: Ref<'w, Components<T>>) -> Self {
+        ComponentVecRef {
+            entities,
+            components,
+        }
+    }
+
+    pub fn get(&self, entity: Entity) -> Option<&T> {
+        let location = self.entities.location(entity)?;
+        self.components.get(entity, location)
+    }
+
+    /// Iterates over every entity slot in index order, yielding `None` for dead entities and
+    /// entities without the component.
+    pub fn iter(&self) -> impl Iterator<Item = Option<&T>> + '_ {
+        (0..self.entities.len()).map(move |index| {
+            let (entity, location) = self.entities.location_at(index)?;
+            self.components.get(entity, location)
+        })
+    }
+}
+
+/// Mutably borrowed view of every component of type `T`, indexed by entity like a
+/// `Vec<Option<T>>`. Components are handed out as `Mut<T>`, which marks them as changed when
+/// written through.
+pub struct ComponentVecMut<'w, T> {
+    entities: &'w Entities,
+    components: RefMut<'w, Components<T>>,
+    ticks: &'w [Option<ComponentTicks>],
+    last_run: u32,
+    this_run: u32,
+}
+
+impl<'w, T> ComponentVecMut<'w, T> {
+    pub(crate) fn new(
+        entities: &'w Entities,
+        components: RefMut<'w, Components<T>>,
+        ticks: &'w [Option<ComponentTicks>],
+        last_run: u32,
+        this_run: u32,
+    ) -> Self {
+        ComponentVecMut {
+            entities,
+            components,
+            ticks,
+            last_run,
+            this_run,
+        }
+    }
+
+    pub fn get(&self, entity: Entity) -> Option<&T> {
+        let location = self.entities.location(entity)?;
+        self.components.get(entity, location)
+    }
+
+    pub fn get_mut(&mut self, entity: Entity) -> Option<Mut<'_, T>> {
+        let location = self.entities.location(entity)?;
+        let component = self.components.get_mut(entity, location)?;
+        let ticks = self.ticks[entity.index() as usize].as_ref()?;
+        Some(Mut::new(component, ticks, self.last_run, self.this_run))
+    }
+
+    /// Iterates over every entity slot in index order, yielding `None` for dead entities and
+    /// entities without the component.
+    pub fn iter(&self) -> impl Iterator<Item = Option<&T>> + '_ {
+        (0..self.entities.len()).map(move |index| {
+            let (entity, location) = self.entities.location_at(index)?;
+            self.components.get(entity, location)
+        })
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+        IterMut {
+            entities: self.entities,
+            components: self.components.ptr(),
+            ticks: self.ticks,
+            last_run: self.last_run,
+            this_run: self.this_run,
+            index: 0,
+            marker: PhantomData,
+        }
+    }
+}
+
+pub struct IterMut<'a, T> {
+    entities: &'a Entities,
+    components: ComponentsPtr<T>,
+    ticks: &'a [Option<ComponentTicks>],
+    last_run: u32,
+    this_run: u32,
+    index: usize,
+    marker: PhantomData<&'a mut T>,
+}
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = Option<Mut<'a, T>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index >= self.entities.len() {
+            return None;
+        }
+        let index = self.index;
+        self.index += 1;
+
+        // Every alive entity has its own component, and each index is visited once, so no
+        // other reference to this component is handed out
+        let component = self
+            .entities
+            .location_at(index)
+            .and_then(|(entity, location)| unsafe { self.components.get(entity, location) })
+            .and_then(|component| {
+                let ticks = self.ticks[index].as_ref()?;
+                Some(Mut::new(component, ticks, self.last_run, self.this_run))
+            });
+        Some(component)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Changed, Component, Entity, StorageType, With, Without, World};
+
+    struct Position(i32);
+    impl Component for Position {}
+
+    struct Stunned(u32);
+    impl Component for Stunned {
+        const STORAGE: StorageType = StorageType::SparseSet;
+    }
+
+    struct Health(i32);
+    impl Component for Health {
+        const STORAGE: StorageType = StorageType::Dense;
+    }
+
+    #[test]
+    fn sparse_components_do_not_move_entities() {
+        let mut world = World::new();
+        let entity = world.new_entity();
+        world.add_component_to_entity(entity, Position(1));
+        let archetypes = world.archetypes().count();
+
+        world.add_component_to_entity(entity, Stunned(3));
+        world.add_component_to_entity(entity, Health(10));
+        assert_eq!(world.archetypes().count(), archetypes);
+
+        assert_eq!(world.remove_component::<Stunned>(entity).unwrap().0, 3);
+        assert!(!world.has::<Stunned>(entity));
+        assert!(world.has::<Health>(entity));
+    }
+
+    #[test]
+    fn queries_mix_storage_types() {
+        let mut world = World::new();
+        let entities: Vec<Entity> = (0..4)
+            .map(|i| {
+                let entity = world.new_entity();
+                world.add_component_to_entity(entity, Position(i));
+                world.add_component_to_entity(entity, Health(i * 10));
+                entity
+            })
+            .collect();
+        world.add_component_to_entity(entities[1], Stunned(1));
+        world.add_component_to_entity(entities[3], Stunned(1));
+        world.remove_component::<Stunned>(entities[1]);
+        world.add_component_to_entity(entities[2], Stunned(2));
+
+        for (mut health, stunned) in world.query::<(&mut Health, &Stunned)>().iter() {
+            health.0 += stunned.0 as i32;
+        }
+        let healths: Vec<i32> = world
+            .query::<&Health>()
+            .iter()
+            .map(|health| health.0)
+            .collect();
+        assert_eq!(healths, vec![0, 10, 22, 31]);
+
+        let unstunned: Vec<Entity> = world
+            .query_filtered::<Entity, (With<Position>, Without<Stunned>)>()
+            .iter()
+            .collect();
+        assert_eq!(unstunned, vec![entities[0], entities[1]]);
+
+        world.clear_trackers();
+        *world.get_mut::<Stunned>(entities[3]).unwrap() = Stunned(5);
+        let changed: Vec<Entity> = world
+            .query_filtered::<Entity, Changed<Stunned>>()
+            .iter()
+            .collect();
+        assert_eq!(changed, vec![entities[3]]);
+    }
+
+    #[test]
+    fn despawn_clears_every_storage_type() {
+        let mut world = World::new();
+        let entity = world.new_entity();
+        world.add_component_to_entity(entity, Stunned(1));
+        world.add_component_to_entity(entity, Health(1));
+        world.despawn(entity);
+
+        // The recycled index must not inherit the despawned entity's components
+        let recycled = world.new_entity();
+        assert_eq!(recycled.index(), entity.index());
+        assert!(!world.has::<Stunned>(recycled));
+        assert!(!world.has::<Health>(recycled));
+        assert_eq!(world.query::<&Stunned>().iter().count(), 0);
+    }
+}
@@ -0,0 +1,327 @@
+use std::any::type_name;
+use std::marker::PhantomData;
+
+use crate::{Access, Query, QueryFilter, World, WorldQuery};
+
+/// Logic that runs against a `World`, usually once per tick as part of a `Schedule`.
+///
+/// Functions whose parameters all implement `SystemParam` are turned into systems by
+/// `IntoSystem`, so implementing this trait by hand is rarely needed. Systems implemented by
+/// hand must report everything they borrow in `access`, or they may fail to borrow it when
+/// running alongside other systems.
+pub trait System: Send + 'static {
+    fn name(&self) -> &str;
+
+    /// Prepares the system to run against `world`. Called once before the first run.
+    fn initialize(&mut self, world: &mut World);
+
+    /// The component types the system reads and writes, known once it is initialized.
+    fn access(&self) -> &Access;
+
+    /// Runs the system. Components changed since its previous run match its `Changed` filters.
+    fn run(&mut self, world: &World);
+
+    /// Applies changes the system deferred because they need `&mut World`. Called at the end of
+    /// every stage the system ran in.
+    fn apply_deferred(&mut self, _world: &mut World) {}
+}
+
+/// A parameter of a function system, fetched from the world every time the system runs.
+pub trait SystemParam {
+    /// State kept by the system between runs.
+    type State: Send + 'static;
+    type Item<'w, 's>;
+
+    /// Creates the state, adding what the parameter borrows to the system's `access`.
+    fn init_state(world: &mut World, access: &mut Access) -> Self::State;
+
+    /// Fetches the parameter. Change filters match components added or changed after `last_run`,
+    /// and components written through the parameter are marked as changed at `this_run`.
+    fn get_param<'w, 's>(
+        state: &'s mut Self::State,
+        world: &'w World,
+        last_run: u32,
+        this_run: u32,
+    ) -> Self::Item<'w, 's>;
+
+    fn apply(_state: &mut Self::State, _world: &mut World) {}
+}
+
+pub type SystemParamItem<'w, 's, P> = <P as SystemParam>::Item<'w, 's>;
+
+// Adds the access of parameter `P` to the access of its system
+pub(crate) fn add_param_access<P: ?Sized>(access: &mut Access, param_access: &Access) {
+    // Conflicting parameters of the same system would fail to borrow on every run
+    if let Some(conflict) = access.conflicts(param_access).next() {
+        panic!(
+            "{} conflicts with a previous parameter over {}",
+            type_name::<P>(),
+            conflict
+        );
+    }
+    access.extend(param_access);
+}
+
+impl<Q: WorldQuery + 'static, F: QueryFilter + 'static> SystemParam for Query<'_, Q, F> {
+    type State = ();
+    type Item<'w, 's> = Query<'w, Q, F>;
+
+    fn init_state(_world: &mut World, access: &mut Access) -> Self::State {
+        let mut query_access = Access::new();
+        Q::update_access(&mut query_access);
+        F::update_access(&mut query_access);
+        add_param_access::<Self>(access, &query_access);
+    }
+
+    fn get_param<'w, 's>(
+        _state: &'s mut Self::State,
+        world: &'w World,
+        last_run: u32,
+        this_run: u32,
+    ) -> Self::Item<'w, 's> {
+        Query::new(world, last_run, this_run)
+    }
+}
+
+macro_rules! impl_system_param_tuple {
+    ($($name:ident),*) => {
+        #[allow(non_snake_case, unused_variables, clippy::unused_unit)]
+        impl<$($name: SystemParam),*> SystemParam for ($($name,)*) {
+            type State = ($($name::State,)*);
+            type Item<'w, 's> = ($($name::Item<'w, 's>,)*);
+
+            fn init_state(world: &mut World, access: &mut Access) -> Self::State {
+                ($($name::init_state(world, access),)*)
+            }
+
+            fn get_param<'w, 's>(
+                state: &'s mut Self::State,
+                world: &'w World,
+                last_run: u32,
+                this_run: u32,
+            ) -> Self::Item<'w, 's> {
+                let ($($name,)*) = state;
+                ($($name::get_param($name, world, last_run, this_run),)*)
+            }
+
+            fn apply(state: &mut Self::State, world: &mut World) {
+                let ($($name,)*) = state;
+                $($name::apply($name, world);)*
+            }
+        }
+    };
+}
+
+impl_system_param_tuple!();
+impl_system_param_tuple!(A);
+impl_system_param_tuple!(A, B);
+impl_system_param_tuple!(A, B, C);
+impl_system_param_tuple!(A, B, C, D);
+impl_system_param_tuple!(A, B, C, D, E);
+impl_system_param_tuple!(A, B, C, D, E, F);
+impl_system_param_tuple!(A, B, C, D, E, F, G);
+impl_system_param_tuple!(A, B, C, D, E, F, G, H);
+
+/// A function that can run as a system, implemented for functions of up to eight parameters
+/// that all implement `SystemParam`. `Marker` only keeps the implementations for different
+/// parameter lists apart.
+pub trait SystemParamFunction<Marker>: Send + 'static {
+    type Param: SystemParam;
+
+    fn run(&mut self, param: SystemParamItem<'_, '_, Self::Param>);
+}
+
+macro_rules! impl_system_param_function {
+    ($($name:ident),*) => {
+        #[allow(non_snake_case, clippy::too_many_arguments)]
+        impl<Func, $($name: SystemParam),*> SystemParamFunction<fn($($name,)*)> for Func
+        where
+            Func: Send + 'static,
+            // The function must accept both the parameter types it was declared with, to infer
+            // them, and the items fetched for any lifetime, to be called with them
+            for<'a> &'a mut Func: FnMut($($name),*) + FnMut($(SystemParamItem<$name>),*),
+        {
+            type Param = ($($name,)*);
+
+            fn run(&mut self, param: SystemParamItem<'_, '_, Self::Param>) {
+                // Calling through a generic function makes the compiler pick the `FnMut`
+                // implementation for the fetched items
+                fn call_inner<$($name),*>(mut f: impl FnMut($($name),*), $($name: $name),*) {
+                    f($($name),*)
+                }
+                let ($($name,)*) = param;
+                call_inner(self, $($name),*)
+            }
+        }
+    };
+}
+
+impl_system_param_function!();
+impl_system_param_function!(A);
+impl_system_param_function!(A, B);
+impl_system_param_function!(A, B, C);
+impl_system_param_function!(A, B, C, D);
+impl_system_param_function!(A, B, C, D, E);
+impl_system_param_function!(A, B, C, D, E, F);
+impl_system_param_function!(A, B, C, D, E, F, G);
+impl_system_param_function!(A, B, C, D, E, F, G, H);
+
+/// Conversion into a `System`, implemented for systems themselves and for functions taking
+/// system parameters, e.g. `fn heal(mut healths: Query<&mut Health>)`.
+pub trait IntoSystem<Marker> {
+    type System: System;
+
+    fn into_system(self) -> Self::System;
+}
+
+impl<S: System> IntoSystem<()> for S {
+    type System = S;
+
+    fn into_system(self) -> Self::System {
+        self
+    }
+}
+
+#[doc(hidden)]
+pub struct IsFunctionSystem;
+
+impl<Marker: 'static, F: SystemParamFunction<Marker>> IntoSystem<(IsFunctionSystem, Marker)> for F {
+    type System = FunctionSystem<Marker, F>;
+
+    fn into_system(self) -> Self::System {
+        FunctionSystem {
+            func: self,
+            state: None,
+            access: Access::new(),
+            last_run: 0,
+            marker: PhantomData,
+        }
+    }
+}
+
+/// A system running a function, created by `IntoSystem`.
+pub struct FunctionSystem<Marker, F: SystemParamFunction<Marker>> {
+    func: F,
+    state: Option<<F::Param as SystemParam>::State>,
+    access: Access,
+    // Tick of the previous run, so change filters only match changes the system has not seen
+    last_run: u32,
+    marker: PhantomData<fn() -> Marker>,
+}
+
+impl<Marker: 'static, F: SystemParamFunction<Marker>> System for FunctionSystem<Marker, F> {
+    fn name(&self) -> &str {
+        type_name::<F>()
+    }
+
+    fn initialize(&mut self, world: &mut World) {
+        if self.state.is_none() {
+            self.state = Some(F::Param::init_state(world, &mut self.access));
+        }
+    }
+
+    fn access(&self) -> &Access {
+        &self.access
+    }
+
+    fn run(&mut self, world: &World) {
+        let this_run = world.change_tick();
+        let state = self
+            .state
+            .as_mut()
+            .expect("systems must be initialized before they run");
+        let param = F::Param::get_param(state, world, self.last_run, this_run);
+        self.func.run(param);
+        self.last_run = this_run;
+    }
+
+    fn apply_deferred(&mut self, world: &mut World) {
+        if let Some(state) = &mut self.state {
+            F::Param::apply(state, world);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::any::TypeId;
+    use std::sync::{Arc, Mutex};
+
+    use crate::{Changed, Component, IntoSystem, Query, System, World};
+
+    struct Health(i32);
+    impl Component for Health {}
+    struct Name(&'static str);
+    impl Component for Name {}
+
+    fn heal(mut query: Query<(&mut Health, &Name)>) {
+        for (mut health, _name) in query.iter() {
+            health.0 = 100;
+        }
+    }
+
+    #[test]
+    fn functions_run_as_systems() {
+        let mut world = World::new();
+        let somebody = world.new_entity();
+        world.add_component_to_entity(somebody, Name("Somebody"));
+        world.add_component_to_entity(somebody, Health(10));
+        let nameless = world.new_entity();
+        world.add_component_to_entity(nameless, Health(5));
+
+        let mut system = heal.into_system();
+        system.initialize(&mut world);
+        system.run(&world);
+
+        assert_eq!(world.get::<Health>(somebody).unwrap().0, 100);
+        assert_eq!(world.get::<Health>(nameless).unwrap().0, 5);
+    }
+
+    #[test]
+    fn systems_see_changes_since_their_last_run() {
+        let mut world = World::new();
+        let entity = world.new_entity();
+        world.add_component_to_entity(entity, Health(10));
+
+        let counts = Arc::new(Mutex::new(Vec::new()));
+        let recorded = counts.clone();
+        let mut system = (move |mut query: Query<&Health, Changed<Health>>| {
+            recorded.lock().unwrap().push(query.iter().count());
+        })
+        .into_system();
+        system.initialize(&mut world);
+
+        // Added before the first run, so the component is new to the system
+        system.run(&world);
+        world.clear_trackers();
+        system.run(&world);
+        world.clear_trackers();
+        world.get_mut::<Health>(entity).unwrap().0 = 5;
+        system.run(&world);
+
+        assert_eq!(*counts.lock().unwrap(), vec![1, 0, 1]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn conflicting_parameters_panic() {
+        let mut system = (|_: Query<&Health>, _: Query<&mut Health>| {}).into_system();
+        system.initialize(&mut World::new());
+    }
+
+    #[test]
+    fn access_covers_every_parameter() {
+        let mut system = (|_: Query<(&mut Health, Option<&Name>)>| {}).into_system();
+        system.initialize(&mut World::new());
+
+        let access = system.access();
+        assert_eq!(
+            access.writes().collect::<Vec<_>>(),
+            vec![TypeId::of::<Health>()]
+        );
+        assert_eq!(
+            access.reads().collect::<Vec<_>>(),
+            vec![TypeId::of::<Name>()]
+        );
+    }
+}
@@ -0,0 +1,290 @@
+//! Local and world transforms of entities in the hierarchy, behind the `transform` feature.
+//!
+//! Entities get a `Transform`, relative to their parent, and a `GlobalTransform`, relative to the
+//! world, which `propagate_transforms` computes once per run for the subtrees that changed.
+//!
+//! ```
+//! use becs::transform::{propagate_transforms, GlobalTransform, Transform, TransformBundle};
+//! use becs::{Schedule, World};
+//!
+//! let mut schedule = Schedule::new();
+//! schedule
+//!     .add_stage("post_update")
+//!     .add_system_to_stage("post_update", propagate_transforms);
+//!
+//! let mut world = World::new();
+//! let ship = world.spawn(TransformBundle::from_transform(Transform::from_translation([
+//!     10.0, 0.0, 0.0,
+//! ])));
+//! let turret = world.spawn(TransformBundle::from_transform(Transform::from_translation([
+//!     0.0, 2.0, 0.0,
+//! ])));
+//! world.set_parent(turret, ship);
+//!
+//! schedule.run(&mut world);
+//! let global = world.get::<GlobalTransform>(turret).unwrap();
+//! assert_eq!(global.translation, [10.0, 2.0, 0.0]);
+//! ```
+
+use std::ops::Deref;
+
+use crate::{
+    bundle, Changed, Children, Component, Entity, Or, Parent, Query, RemovedComponents, With,
+    Without,
+};
+
+/// Translation, rotation and uniform scale, applied in reverse order.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub translation: [f32; 3],
+    /// Unit quaternion, as `[x, y, z, w]`.
+    pub rotation: [f32; 4],
+    pub scale: f32,
+}
+
+impl Transform {
+    pub const IDENTITY: Transform = Transform {
+        translation: [0.0; 3],
+        rotation: [0.0, 0.0, 0.0, 1.0],
+        scale: 1.0,
+    };
+
+    pub fn from_translation(translation: [f32; 3]) -> Self {
+        Transform {
+            translation,
+            ..Transform::IDENTITY
+        }
+    }
+
+    /// Rotation by `angle` radians around the unit vector `axis`.
+    pub fn from_axis_angle(axis: [f32; 3], angle: f32) -> Self {
+        let (sin, cos) = (angle / 2.0).sin_cos();
+        Transform {
+            rotation: [axis[0] * sin, axis[1] * sin, axis[2] * sin, cos],
+            ..Transform::IDENTITY
+        }
+    }
+
+    pub fn with_scale(self, scale: f32) -> Self {
+        Transform { scale, ..self }
+    }
+
+    /// Applies `local` first, then this transform, e.g. to place a child relative to the world
+    /// from its parent's transform.
+    pub fn mul_transform(&self, local: &Transform) -> Transform {
+        Transform {
+            translation: self.transform_point(local.translation),
+            rotation: quat_mul(self.rotation, local.rotation),
+            scale: self.scale * local.scale,
+        }
+    }
+
+    pub fn transform_point(&self, point: [f32; 3]) -> [f32; 3] {
+        let scaled = point.map(|coordinate| coordinate * self.scale);
+        let rotated = quat_rotate(self.rotation, scaled);
+        [0, 1, 2].map(|i| rotated[i] + self.translation[i])
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform::IDENTITY
+    }
+}
+
+impl Component for Transform {}
+
+/// Transform of an entity relative to the world, written by `propagate_transforms`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct GlobalTransform(Transform);
+
+impl Deref for GlobalTransform {
+    type Target = Transform;
+
+    fn deref(&self) -> &Transform {
+        &self.0
+    }
+}
+
+impl Component for GlobalTransform {}
+
+bundle! {
+    /// The two transforms every entity taking part in propagation needs.
+    #[derive(Default)]
+    pub struct TransformBundle {
+        pub transform: Transform,
+        pub global: GlobalTransform,
+    }
+}
+
+impl TransformBundle {
+    pub fn from_transform(transform: Transform) -> Self {
+        TransformBundle {
+            transform,
+            global: GlobalTransform(transform),
+        }
+    }
+}
+
+type Nodes<'w> = Query<
+    'w,
+    (
+        &'static Transform,
+        &'static mut GlobalTransform,
+        Option<&'static Children>,
+    ),
+>;
+type Dirty<'w> = Query<'w, Entity, Or<(Changed<Transform>, Changed<Parent>)>>;
+
+/// Updates the `GlobalTransform` of every entity below a root, an entity with a `Transform` but
+/// no `Parent`.
+///
+/// Only subtrees whose root changed `Transform`, gained a parent or lost one are recomputed, so
+/// untouched `GlobalTransform`s do not match `Changed`. The propagation stops at entities
+/// without both transforms.
+pub fn propagate_transforms(
+    mut roots: Query<Entity, (With<Transform>, Without<Parent>)>,
+    mut nodes: Nodes<'_>,
+    mut dirty: Dirty<'_>,
+    unparented: RemovedComponents<Parent>,
+) {
+    let unparented: Vec<Entity> = unparented.iter().collect();
+    let roots: Vec<Entity> = roots.iter().collect();
+    for root in roots {
+        let changed = unparented.contains(&root);
+        propagate(root, &Transform::IDENTITY, changed, &mut nodes, &mut dirty);
+    }
+}
+
+fn propagate(
+    entity: Entity,
+    parent: &Transform,
+    parent_changed: bool,
+    nodes: &mut Nodes<'_>,
+    dirty: &mut Dirty<'_>,
+) {
+    let changed = parent_changed || dirty.get(entity).is_some();
+    let (global, children) = match nodes.get(entity) {
+        Some((transform, mut global, children)) => {
+            if changed {
+                global.0 = parent.mul_transform(transform);
+            }
+            (global.0, children.map(|children| children.to_vec()))
+        }
+        None => return,
+    };
+    for child in children.into_iter().flatten() {
+        propagate(child, &global, changed, nodes, dirty);
+    }
+}
+
+fn quat_mul(a: [f32; 4], b: [f32; 4]) -> [f32; 4] {
+    let [ax, ay, az, aw] = a;
+    let [bx, by, bz, bw] = b;
+    [
+        aw * bx + ax * bw + ay * bz - az * by,
+        aw * by - ax * bz + ay * bw + az * bx,
+        aw * bz + ax * by - ay * bx + az * bw,
+        aw * bw - ax * bx - ay * by - az * bz,
+    ]
+}
+
+fn quat_rotate(q: [f32; 4], v: [f32; 3]) -> [f32; 3] {
+    // v + 2w(u × v) + 2u × (u × v), with u the vector part of q
+    let u = [q[0], q[1], q[2]];
+    let uv = cross(u, v);
+    let uuv = cross(u, uv);
+    [0, 1, 2].map(|i| v[i] + 2.0 * (q[3] * uv[i] + uuv[i]))
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::{Schedule, World};
+
+    fn approx_eq(a: [f32; 3], b: [f32; 3]) -> bool {
+        a.iter().zip(&b).all(|(a, b)| (a - b).abs() < 1e-5)
+    }
+
+    fn global(world: &World, entity: Entity) -> [f32; 3] {
+        world.get::<GlobalTransform>(entity).unwrap().translation
+    }
+
+    #[test]
+    fn transforms_compose() {
+        let parent = Transform::from_axis_angle([0.0, 0.0, 1.0], FRAC_PI_2)
+            .with_scale(2.0)
+            .mul_transform(&Transform::IDENTITY);
+        let moved = Transform {
+            translation: [1.0, 0.0, 0.0],
+            ..parent
+        };
+        let child = moved.mul_transform(&Transform::from_translation([1.0, 0.0, 0.0]));
+        assert!(approx_eq(child.translation, [1.0, 2.0, 0.0]));
+        assert_eq!(child.scale, 2.0);
+        assert!(approx_eq(
+            child.transform_point([1.0, 0.0, 0.0]),
+            [1.0, 4.0, 0.0]
+        ));
+    }
+
+    #[test]
+    fn only_dirty_subtrees_are_recomputed() {
+        let recomputed = Arc::new(Mutex::new(Vec::new()));
+        let recorded = recomputed.clone();
+        let mut schedule = Schedule::new();
+        schedule
+            .add_stage("post_update")
+            .add_stage("last")
+            .add_system_to_stage("post_update", propagate_transforms)
+            .add_system_to_stage(
+                "last",
+                move |mut changed: Query<Entity, Changed<GlobalTransform>>| {
+                    *recorded.lock().unwrap() = changed.iter().collect();
+                },
+            );
+        let mut run = |world: &mut World| {
+            schedule.run(world);
+            let mut entities = std::mem::take(&mut *recomputed.lock().unwrap());
+            entities.sort_by_key(|entity: &Entity| entity.index());
+            entities
+        };
+
+        let mut world = World::new();
+        let [root, child, grandchild, other] = [1.0, 2.0, 3.0, 4.0].map(|x| {
+            world.spawn(TransformBundle::from_transform(
+                Transform::from_translation([x, 0.0, 0.0]),
+            ))
+        });
+        world.set_parent(child, root);
+        world.set_parent(grandchild, child);
+        assert_eq!(run(&mut world).len(), 4);
+        assert_eq!(global(&world, grandchild), [6.0, 0.0, 0.0]);
+        assert_eq!(global(&world, other), [4.0, 0.0, 0.0]);
+        assert!(run(&mut world).is_empty());
+
+        world.get_mut::<Transform>(child).unwrap().translation[1] = 1.0;
+        assert_eq!(run(&mut world), vec![child, grandchild]);
+        assert_eq!(global(&world, grandchild), [6.0, 1.0, 0.0]);
+
+        // A child taken out of the hierarchy becomes a root of its own
+        world.remove_parent(grandchild);
+        assert_eq!(run(&mut world), vec![grandchild]);
+        assert_eq!(global(&world, grandchild), [3.0, 0.0, 0.0]);
+
+        world.set_parent(other, root);
+        assert_eq!(run(&mut world), vec![other]);
+        assert_eq!(global(&world, other), [5.0, 0.0, 0.0]);
+    }
+}
@@ -0,0 +1,614 @@
+use std::error::Error;
+use std::fmt;
+
+use serde::de::value::StrDeserializer;
+use serde::de::{self, DeserializeOwned, DeserializeSeed, IntoDeserializer, Visitor};
+use serde::ser::{self, Serialize};
+use serde::{forward_to_deserialize_any, Deserialize};
+
+/// A value of a registered type, turned into data that can be written by any serde format and
+/// read back without knowing the type. Every value is tagged with the variant holding it, so
+/// even formats that are not self-describing, such as bincode, can read it back.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, Deserialize)]
+pub(crate) enum Value {
+    Unit,
+    Bool(bool),
+    I64(i64),
+    U64(u64),
+    F64(f64),
+    Char(char),
+    String(String),
+    Bytes(Vec<u8>),
+    None,
+    Some(Box<Value>),
+    Seq(Vec<Value>),
+    Map(Vec<(Value, Value)>),
+    /// An enum variant by name, with its content as `Unit`, a single value, a `Seq` of fields
+    /// or a `Map` of named fields.
+    Variant(String, Box<Value>),
+}
+
+/// Error converting a value to or from a `Value`.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct ValueError(String);
+
+impl fmt::Display for ValueError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Error for ValueError {}
+
+impl ser::Error for ValueError {
+    fn custom<T: fmt::Display>(message: T) -> Self {
+        ValueError(message.to_string())
+    }
+}
+
+impl de::Error for ValueError {
+    fn custom<T: fmt::Display>(message: T) -> Self {
+        ValueError(message.to_string())
+    }
+}
+
+pub(crate) fn to_value<T: Serialize + ?Sized>(value: &T) -> Result<Value, ValueError> {
+    value.serialize(ValueSerializer)
+}
+
+pub(crate) fn from_value<T: DeserializeOwned>(value: &Value) -> Result<T, ValueError> {
+    T::deserialize(ValueDeserializer(value))
+}
+
+struct ValueSerializer;
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = Value;
+    type Error = ValueError;
+    type SerializeSeq = SeqSerializer;
+    type SerializeTuple = SeqSerializer;
+    type SerializeTupleStruct = SeqSerializer;
+    type SerializeTupleVariant = SeqSerializer;
+    type SerializeMap = MapSerializer;
+    type SerializeStruct = MapSerializer;
+    type SerializeStructVariant = MapSerializer;
+
+    fn serialize_bool(self, v: bool) -> Result<Value, ValueError> {
+        Ok(Value::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Value, ValueError> {
+        Ok(Value::I64(v.into()))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Value, ValueError> {
+        Ok(Value::I64(v.into()))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Value, ValueError> {
+        Ok(Value::I64(v.into()))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Value, ValueError> {
+        Ok(Value::I64(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Value, ValueError> {
+        Ok(Value::U64(v.into()))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Value, ValueError> {
+        Ok(Value::U64(v.into()))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Value, ValueError> {
+        Ok(Value::U64(v.into()))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Value, ValueError> {
+        Ok(Value::U64(v))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Value, ValueError> {
+        Ok(Value::F64(v.into()))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Value, ValueError> {
+        Ok(Value::F64(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Value, ValueError> {
+        Ok(Value::Char(v))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Value, ValueError> {
+        Ok(Value::String(v.to_string()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Value, ValueError> {
+        Ok(Value::Bytes(v.to_vec()))
+    }
+
+    fn serialize_none(self) -> Result<Value, ValueError> {
+        Ok(Value::None)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Value, ValueError> {
+        Ok(Value::Some(Box::new(to_value(value)?)))
+    }
+
+    fn serialize_unit(self) -> Result<Value, ValueError> {
+        Ok(Value::Unit)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value, ValueError> {
+        Ok(Value::Unit)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<Value, ValueError> {
+        Ok(Value::Variant(variant.to_string(), Box::new(Value::Unit)))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Value, ValueError> {
+        to_value(value)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Value, ValueError> {
+        Ok(Value::Variant(
+            variant.to_string(),
+            Box::new(to_value(value)?),
+        ))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqSerializer, ValueError> {
+        Ok(SeqSerializer::new(None, len.unwrap_or(0)))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SeqSerializer, ValueError> {
+        Ok(SeqSerializer::new(None, len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SeqSerializer, ValueError> {
+        Ok(SeqSerializer::new(None, len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SeqSerializer, ValueError> {
+        Ok(SeqSerializer::new(Some(variant), len))
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<MapSerializer, ValueError> {
+        Ok(MapSerializer::new(None, len.unwrap_or(0)))
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<MapSerializer, ValueError> {
+        Ok(MapSerializer::new(None, len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<MapSerializer, ValueError> {
+        Ok(MapSerializer::new(Some(variant), len))
+    }
+}
+
+// Wraps the content of an enum variant with its name
+fn variant(variant: Option<&'static str>, content: Value) -> Value {
+    match variant {
+        Some(name) => Value::Variant(name.to_string(), Box::new(content)),
+        None => content,
+    }
+}
+
+struct SeqSerializer {
+    variant: Option<&'static str>,
+    values: Vec<Value>,
+}
+
+impl SeqSerializer {
+    fn new(variant: Option<&'static str>, len: usize) -> Self {
+        SeqSerializer {
+            variant,
+            values: Vec::with_capacity(len),
+        }
+    }
+
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ValueError> {
+        self.values.push(to_value(value)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Value, ValueError> {
+        Ok(variant(self.variant, Value::Seq(self.values)))
+    }
+}
+
+impl ser::SerializeSeq for SeqSerializer {
+    type Ok = Value;
+    type Error = ValueError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ValueError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, ValueError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for SeqSerializer {
+    type Ok = Value;
+    type Error = ValueError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ValueError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, ValueError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqSerializer {
+    type Ok = Value;
+    type Error = ValueError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ValueError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, ValueError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for SeqSerializer {
+    type Ok = Value;
+    type Error = ValueError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ValueError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, ValueError> {
+        self.finish()
+    }
+}
+
+struct MapSerializer {
+    variant: Option<&'static str>,
+    entries: Vec<(Value, Value)>,
+    key: Option<Value>,
+}
+
+impl MapSerializer {
+    fn new(variant: Option<&'static str>, len: usize) -> Self {
+        MapSerializer {
+            variant,
+            entries: Vec::with_capacity(len),
+            key: None,
+        }
+    }
+
+    fn field<T: Serialize + ?Sized>(&mut self, key: &str, value: &T) -> Result<(), ValueError> {
+        self.entries
+            .push((Value::String(key.to_string()), to_value(value)?));
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Value, ValueError> {
+        Ok(variant(self.variant, Value::Map(self.entries)))
+    }
+}
+
+impl ser::SerializeMap for MapSerializer {
+    type Ok = Value;
+    type Error = ValueError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), ValueError> {
+        self.key = Some(to_value(key)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ValueError> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| ValueError("map value serialized before its key".to_string()))?;
+        self.entries.push((key, to_value(value)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, ValueError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for MapSerializer {
+    type Ok = Value;
+    type Error = ValueError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), ValueError> {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<Value, ValueError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for MapSerializer {
+    type Ok = Value;
+    type Error = ValueError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), ValueError> {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<Value, ValueError> {
+        self.finish()
+    }
+}
+
+#[derive(Clone, Copy)]
+struct ValueDeserializer<'v>(&'v Value);
+
+impl<'de, 'v> de::Deserializer<'de> for ValueDeserializer<'v> {
+    type Error = ValueError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+        match self.0 {
+            Value::Unit => visitor.visit_unit(),
+            Value::Bool(v) => visitor.visit_bool(*v),
+            Value::I64(v) => visitor.visit_i64(*v),
+            Value::U64(v) => visitor.visit_u64(*v),
+            Value::F64(v) => visitor.visit_f64(*v),
+            Value::Char(v) => visitor.visit_char(*v),
+            Value::String(v) => visitor.visit_str(v),
+            Value::Bytes(v) => visitor.visit_bytes(v),
+            Value::None => visitor.visit_none(),
+            Value::Some(v) => visitor.visit_some(ValueDeserializer(v)),
+            Value::Seq(values) => visitor.visit_seq(SeqDeserializer(values.iter())),
+            Value::Map(entries) => visitor.visit_map(MapDeserializer {
+                entries: entries.iter(),
+                value: None,
+            }),
+            Value::Variant(name, content) => visitor.visit_enum(EnumDeserializer(name, content)),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+        match self.0 {
+            Value::None => visitor.visit_none(),
+            Value::Some(v) => visitor.visit_some(ValueDeserializer(v)),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, ValueError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        unit unit_struct seq tuple tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+struct SeqDeserializer<'v>(std::slice::Iter<'v, Value>);
+
+impl<'de, 'v> de::SeqAccess<'de> for SeqDeserializer<'v> {
+    type Error = ValueError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, ValueError> {
+        self.0
+            .next()
+            .map(|value| seed.deserialize(ValueDeserializer(value)))
+            .transpose()
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.0.len())
+    }
+}
+
+struct MapDeserializer<'v> {
+    entries: std::slice::Iter<'v, (Value, Value)>,
+    value: Option<&'v Value>,
+}
+
+impl<'de, 'v> de::MapAccess<'de> for MapDeserializer<'v> {
+    type Error = ValueError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, ValueError> {
+        match self.entries.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                seed.deserialize(ValueDeserializer(key)).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, ValueError> {
+        let value = self
+            .value
+            .take()
+            .ok_or_else(|| ValueError("map value deserialized before its key".to_string()))?;
+        seed.deserialize(ValueDeserializer(value))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.entries.len())
+    }
+}
+
+struct EnumDeserializer<'v>(&'v str, &'v Value);
+
+impl<'de, 'v> de::EnumAccess<'de> for EnumDeserializer<'v> {
+    type Error = ValueError;
+    type Variant = ValueDeserializer<'v>;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, ValueDeserializer<'v>), ValueError> {
+        let name: StrDeserializer<'_, ValueError> = self.0.into_deserializer();
+        Ok((seed.deserialize(name)?, ValueDeserializer(self.1)))
+    }
+}
+
+impl<'de, 'v> de::VariantAccess<'de> for ValueDeserializer<'v> {
+    type Error = ValueError;
+
+    fn unit_variant(self) -> Result<(), ValueError> {
+        match self.0 {
+            Value::Unit => Ok(()),
+            _ => Err(ValueError("expected a unit variant".to_string())),
+        }
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<T::Value, ValueError> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, ValueError> {
+        de::Deserializer::deserialize_any(self, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, ValueError> {
+        de::Deserializer::deserialize_any(self, visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde::{Deserialize, Serialize};
+
+    use super::{from_value, to_value, Value};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Shape {
+        Point,
+        Circle(f32),
+        Line(i8, i8),
+        Rect { width: u16, height: u16 },
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Drawing {
+        name: String,
+        shapes: Vec<Shape>,
+        layer: Option<u8>,
+        tags: HashMap<String, char>,
+        id: Id,
+        hidden: (),
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Id(u64);
+
+    #[test]
+    fn values_round_trip_through_formats() {
+        let drawing = Drawing {
+            name: "sketch".to_string(),
+            shapes: vec![
+                Shape::Point,
+                Shape::Circle(1.5),
+                Shape::Line(-1, 2),
+                Shape::Rect {
+                    width: 3,
+                    height: 4,
+                },
+            ],
+            layer: Some(2),
+            tags: std::iter::once(("mark".to_string(), 'x')).collect(),
+            id: Id(u64::MAX),
+            hidden: (),
+        };
+        let value = to_value(&drawing).unwrap();
+        assert_eq!(from_value::<Drawing>(&value).unwrap(), drawing);
+
+        let ron = ron::to_string(&value).unwrap();
+        assert_eq!(
+            from_value::<Drawing>(&ron::from_str(&ron).unwrap()).unwrap(),
+            drawing
+        );
+        let bytes = bincode::serialize(&value).unwrap();
+        let read: Value = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(from_value::<Drawing>(&read).unwrap(), drawing);
+    }
+
+    #[test]
+    fn mismatched_types_fail() {
+        let value = to_value(&Shape::Circle(1.0)).unwrap();
+        assert!(from_value::<Drawing>(&value).is_err());
+        assert!(from_value::<u8>(&to_value(&300u32).unwrap()).is_err());
+    }
+}